@@ -0,0 +1,37 @@
+// A compile-time benchmark, all the work happens while type-checking this
+// file. Time it with `cargo build --release --example fact` after touching it.
+#![recursion_limit = "2048"]
+
+use stlcrts::*;
+use stlcrts_macros::stlc;
+
+fn main() {
+    eval_to::<
+        stlc! {
+            let rec add (a: Nat) (b: Nat) : Nat =
+                if iszero a then b else succ (add (pred a) b)
+            in
+            let rec mul (a: Nat) (b: Nat) : Nat =
+                if iszero a then 0 else add b (mul (pred a) b)
+            in
+            mul 7 9
+        },
+        stlc! { 63 },
+    >();
+
+    eval_to::<
+        stlc! {
+            let rec add (a: Nat) (b: Nat) : Nat =
+                if iszero a then b else succ (add (pred a) b)
+            in
+            let rec mul (a: Nat) (b: Nat) : Nat =
+                if iszero a then 0 else add b (mul (pred a) b)
+            in
+            let rec fact (a: Nat) : Nat =
+                if iszero a then 1 else mul a (fact (pred a))
+            in
+            fact 4
+        },
+        stlc! { 24 },
+    >();
+}
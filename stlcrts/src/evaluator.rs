@@ -1,284 +1,494 @@
-use crate::{
-    evaluator::{shiftby1::ShiftBy1, shiftbyneg1::ShiftByNeg1, subst::Subst},
-    term::*,
-};
+use crate::{evaluator::instantiate::Instantiate, term::*};
 use std::marker::PhantomData;
 
 pub trait Value: Term {}
 impl Value for True {}
 impl Value for False {}
 impl Value for Zero {}
-impl<N: Term> Value for Succ<N> where N: Value {}
+impl Value for Triv {}
+impl<I: Index> Value for Loc<I> {}
+impl<K: Stack> Value for ContV<K> {}
+impl<N: Term> Value for Succ<N> where N: Plain {}
 impl<Tp: Type, T: Term> Value for Lam<Tp, T> {}
+impl<T: Term> Value for CLam<T> {}
+impl<T: Term> Value for ULam<T> {}
+impl<Fs: FieldList + FieldValues> Value for Record<Fs> {}
+impl<L: Label, V: Plain, Tp: Type> Value for Tag<L, V, Tp> {}
+impl<Tp: Type> Value for Nil<Tp> {}
+impl Value for CNil {}
+impl<H: Plain, T: Plain> Value for Cons<H, T> {}
+impl<H: Type, V: Plain, ExTp: Type> Value for Pack<H, V, ExTp> {}
+impl<V: Plain, G: Ground> Value for Cast<V, G, Dyn> {}
+impl<V: Plain, A: Type, B: Type, C: Type, D: Type> Value for Cast<V, Arrow<A, B>, Arrow<C, D>> {}
+// An uncaught exception is also a result of the evaluation
+impl<V: Plain> Value for Raise<V> {}
+// And so is a failed cast
+impl Value for Blame {}
 
-mod shiftby1 {
+// Values which are not exceptions, nor contain one
+pub(crate) trait Plain: Value {}
+impl Plain for True {}
+impl Plain for False {}
+impl Plain for Zero {}
+impl Plain for Triv {}
+impl<I: Index> Plain for Loc<I> {}
+impl<K: Stack> Plain for ContV<K> {}
+impl<N: Term> Plain for Succ<N> where N: Plain {}
+impl<Tp: Type, T: Term> Plain for Lam<Tp, T> {}
+impl<T: Term> Plain for CLam<T> {}
+impl<T: Term> Plain for ULam<T> {}
+impl<Fs: FieldList + FieldValues> Plain for Record<Fs> {}
+impl<L: Label, V: Plain, Tp: Type> Plain for Tag<L, V, Tp> {}
+impl<Tp: Type> Plain for Nil<Tp> {}
+impl Plain for CNil {}
+impl<H: Plain, T: Plain> Plain for Cons<H, T> {}
+impl<H: Type, V: Plain, ExTp: Type> Plain for Pack<H, V, ExTp> {}
+impl<V: Plain, G: Ground> Plain for Cast<V, G, Dyn> {}
+impl<V: Plain, A: Type, B: Type, C: Type, D: Type> Plain for Cast<V, Arrow<A, B>, Arrow<C, D>> {}
+
+// Records whose fields are all values
+pub(crate) trait FieldValues {}
+impl FieldValues for FieldNil {}
+impl<L: Label, V: Plain, Tl: FieldList + FieldValues> FieldValues for FieldCons<L, V, Tl> {}
+
+// Shifting and substituting are the same structural map over terms, they
+// only differ on variables. TermMap<F, C> rebuilds a term, counting in C the
+// binders it went under, and hands each variable to the action F with
+// MapVar. A new term constructor only needs a TermMap impl.
+pub(crate) mod termmap {
     use super::*;
 
-    pub trait ShiftBy1 {
+    pub trait TermMap<F, C: Index> {
         type Res: Term;
     }
-    trait Shift<C: Index> {
+    // The action F on the variable Var<I>, under C binders
+    pub trait MapVar<I: Index, C: Index> {
         type Res: Term;
     }
-    impl<T: Term, R: Term> ShiftBy1 for T
-    where
-        T: Shift<I0, Res = R>,
-    {
-        type Res = R;
-    }
 
-    impl<C: Index> Shift<C> for True {
+    impl<F, C: Index> TermMap<F, C> for True {
         type Res = True;
     }
-    impl<C: Index> Shift<C> for False {
+    impl<F, C: Index> TermMap<F, C> for False {
         type Res = False;
     }
 
-    impl<C: Index> Shift<C> for Zero {
+    impl<F, C: Index> TermMap<F, C> for Zero {
         type Res = Zero;
     }
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for Succ<T>
+    impl<F, C: Index> TermMap<F, C> for Triv {
+        type Res = Triv;
+    }
+    impl<F, C: Index, I: Index> TermMap<F, C> for Loc<I> {
+        type Res = Loc<I>;
+    }
+    impl<F, C: Index, K: Stack> TermMap<F, C> for ContV<K> {
+        type Res = ContV<K>;
+    }
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Succ<T>
     where
-        T: Shift<C, Res = Tprime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
         type Res = Succ<Tprime>;
     }
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for Pred<T>
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Pred<T>
     where
-        T: Shift<C, Res = Tprime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
         type Res = Pred<Tprime>;
     }
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for IsZero<T>
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for IsZero<T>
     where
-        T: Shift<C, Res = Tprime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
         type Res = IsZero<Tprime>;
     }
 
-    trait ShiftVar<C: Index> {
-        type Res: Index;
+    impl<F: MapVar<I, C>, C: Index, I: Index> TermMap<F, C> for Var<I> {
+        type Res = F::Res;
     }
-    impl<K: Index> ShiftVar<I0> for K {
-        type Res = ISucc<K>;
+
+    impl<F, C: Index, Tp: Type, T: Term, R: Term> TermMap<F, C> for Lam<Tp, T>
+    where
+        T: TermMap<F, ISucc<C>, Res = R>,
+    {
+        type Res = Lam<Tp, R>;
     }
-    impl<N: Index> ShiftVar<ISucc<N>> for I0 {
-        type Res = I0;
+
+    impl<F, C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term> TermMap<F, C> for App<T1, T2>
+    where
+        T1: TermMap<F, C, Res = T1prime>,
+        T2: TermMap<F, C, Res = T2prime>,
+    {
+        type Res = App<T1prime, T2prime>;
     }
-    impl<N: Index, K: Index, R: Index> ShiftVar<ISucc<N>> for ISucc<K>
+
+    impl<F, C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term, T3: Term, T3prime: Term>
+        TermMap<F, C> for If<T1, T2, T3>
     where
-        K: ShiftVar<N, Res = R>,
+        T1: TermMap<F, C, Res = T1prime>,
+        T2: TermMap<F, C, Res = T2prime>,
+        T3: TermMap<F, C, Res = T3prime>,
     {
-        type Res = ISucc<R>;
+        type Res = If<T1prime, T2prime, T3prime>;
     }
 
-    impl<C: Index, I: Index, R: Index> Shift<C> for Var<I>
+    impl<F, C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term> TermMap<F, C> for Let<T1, T2>
     where
-        I: ShiftVar<C, Res = R>,
+        T1: TermMap<F, C, Res = T1prime>,
+        T2: TermMap<F, ISucc<C>, Res = T2prime>,
     {
-        type Res = Var<R>;
+        type Res = Let<T1prime, T2prime>;
     }
 
-    impl<C: Index, Tp: Type, T: Term, R: Term> Shift<C> for Lam<Tp, T>
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Fix<T>
     where
-        T: Shift<ISucc<C>, Res = R>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = Lam<Tp, R>;
+        type Res = Fix<Tprime>;
     }
 
-    impl<C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term> Shift<C> for App<T1, T2>
+    impl<F, C: Index, Z: Term, Zprime: Term, S: Term, Sprime: Term, N: Term, Nprime: Term>
+        TermMap<F, C> for NatRec<Z, S, N>
     where
-        T1: Shift<C, Res = T1prime>,
-        T2: Shift<C, Res = T2prime>,
+        Z: TermMap<F, C, Res = Zprime>,
+        S: TermMap<F, C, Res = Sprime>,
+        N: TermMap<F, C, Res = Nprime>,
     {
-        type Res = App<T1prime, T2prime>;
+        type Res = NatRec<Zprime, Sprime, Nprime>;
     }
 
-    impl<C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term, T3: Term, T3prime: Term>
-        Shift<C> for If<T1, T2, T3>
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Ref<T>
     where
-        T1: Shift<C, Res = T1prime>,
-        T2: Shift<C, Res = T2prime>,
-        T3: Shift<C, Res = T3prime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = If<T1prime, T2prime, T3prime>;
+        type Res = Ref<Tprime>;
     }
 
-    impl<C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term> Shift<C> for Let<T1, T2>
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Deref<T>
     where
-        T1: Shift<C, Res = T1prime>,
-        T2: Shift<ISucc<C>, Res = T2prime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = Let<T1prime, T2prime>;
+        type Res = Deref<Tprime>;
     }
 
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for Fix<T>
+    impl<F, C: Index, L: Term, Lprime: Term, T: Term, Tprime: Term> TermMap<F, C> for Assign<L, T>
     where
-        T: Shift<C, Res = Tprime>,
+        L: TermMap<F, C, Res = Lprime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = Fix<Tprime>;
+        type Res = Assign<Lprime, Tprime>;
     }
-}
 
-mod subst {
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for CallCC<T>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+    {
+        type Res = CallCC<Tprime>;
+    }
 
-    use crate::evaluator::shiftby1::ShiftBy1;
+    impl<F, C: Index, K: Term, Kprime: Term, V: Term, Vprime: Term> TermMap<F, C> for Throw<K, V>
+    where
+        K: TermMap<F, C, Res = Kprime>,
+        V: TermMap<F, C, Res = Vprime>,
+    {
+        type Res = Throw<Kprime, Vprime>;
+    }
 
-    use super::*;
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Print<T>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+    {
+        type Res = Print<Tprime>;
+    }
 
-    pub trait Subst<J: Index, S: Term> {
-        type Res: Term;
+    impl<F, C: Index, T: Term, Tprime: Term> TermMap<F, C> for Raise<T>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+    {
+        type Res = Raise<Tprime>;
     }
-    impl<J: Index, S: Term> Subst<J, S> for True {
-        type Res = True;
+
+    impl<F, C: Index, T: Term, Tprime: Term, H: Term, Hprime: Term> TermMap<F, C> for Try<T, H>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+        H: TermMap<F, ISucc<C>, Res = Hprime>,
+    {
+        type Res = Try<Tprime, Hprime>;
     }
-    impl<J: Index, S: Term> Subst<J, S> for False {
-        type Res = False;
+
+    impl<F, C: Index, T: Term, Tprime: Term, Tp: Type> TermMap<F, C> for Ann<T, Tp>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+    {
+        type Res = Ann<Tprime, Tp>;
     }
 
-    impl<J: Index, S: Term> Subst<J, S> for Zero {
-        type Res = Zero;
+    impl<F, C: Index, T: Term, R: Term> TermMap<F, C> for CLam<T>
+    where
+        T: TermMap<F, ISucc<C>, Res = R>,
+    {
+        type Res = CLam<R>;
     }
-    impl<J: Index, S: Term, T: Term, Tprime: Term> Subst<J, S> for Succ<T>
+
+    impl<F, C: Index, T: Term, R: Term> TermMap<F, C> for ULam<T>
     where
-        T: Subst<J, S, Res = Tprime>,
+        T: TermMap<F, ISucc<C>, Res = R>,
     {
-        type Res = Succ<Tprime>;
+        type Res = ULam<R>;
     }
-    impl<J: Index, S: Term, T: Term, Tprime: Term> Subst<J, S> for Pred<T>
+
+    trait MapFns<F, C: Index> {
+        type Res: FnList;
+    }
+    impl<F, C: Index> MapFns<F, C> for FnNil {
+        type Res = FnNil;
+    }
+    impl<F, C: Index, Tp: Type, T: Term, Tprime: Term, Tl: FnList, Tlprime: FnList> MapFns<F, C>
+        for FnCons<Tp, T, Tl>
     where
-        T: Subst<J, S, Res = Tprime>,
+        T: TermMap<F, C, Res = Tprime>,
+        Tl: MapFns<F, C, Res = Tlprime>,
     {
-        type Res = Pred<Tprime>;
+        type Res = FnCons<Tp, Tprime, Tlprime>;
     }
-    impl<J: Index, S: Term, T: Term, Tprime: Term> Subst<J, S> for IsZero<T>
+
+    impl<F, C: Index, Fs: FnList, Fsprime: FnList, K: Index> TermMap<F, C> for FixN<Fs, K>
     where
-        T: Subst<J, S, Res = Tprime>,
+        Fs: MapFns<F, C, Res = Fsprime>,
     {
-        type Res = IsZero<Tprime>;
+        type Res = FixN<Fsprime, K>;
     }
 
-    trait Select<S: Term, K: Term> {
-        type Res: Term;
+    trait MapFields<F, C: Index> {
+        type Res: FieldList;
     }
-    impl<S: Term, K: Term> Select<S, K> for (I0, I0) {
-        type Res = S;
+    impl<F, C: Index> MapFields<F, C> for FieldNil {
+        type Res = FieldNil;
     }
-    impl<S: Term, K: Term, N: Index> Select<S, K> for (ISucc<N>, I0) {
-        type Res = K;
+    impl<F, C: Index, L: Label, T: Term, Tprime: Term, Tl: FieldList, Tlprime: FieldList>
+        MapFields<F, C> for FieldCons<L, T, Tl>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+        Tl: MapFields<F, C, Res = Tlprime>,
+    {
+        type Res = FieldCons<L, Tprime, Tlprime>;
     }
-    impl<S: Term, K: Term, N: Index> Select<S, K> for (I0, ISucc<N>) {
-        type Res = K;
+
+    impl<F, C: Index, Fs: FieldList, Fsprime: FieldList> TermMap<F, C> for Record<Fs>
+    where
+        Fs: MapFields<F, C, Res = Fsprime>,
+    {
+        type Res = Record<Fsprime>;
     }
-    impl<S: Term, K: Term, N1: Index, N2: Index, R: Term> Select<S, K> for (ISucc<N1>, ISucc<N2>)
+
+    impl<F, C: Index, T: Term, Tprime: Term, L: Label> TermMap<F, C> for Proj<T, L>
     where
-        (N1, N2): Select<S, K, Res = R>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = R;
+        type Res = Proj<Tprime, L>;
     }
 
-    impl<J: Index, S: Term, I: Index, R: Term> Subst<J, S> for Var<I>
+    impl<F, C: Index, L: Label, T: Term, Tprime: Term, Tp: Type> TermMap<F, C> for Tag<L, T, Tp>
     where
-        (I, J): Select<S, Var<I>, Res = R>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = R;
+        type Res = Tag<L, Tprime, Tp>;
     }
 
-    impl<J: Index, S: Term, Tp: Type, T: Term, Sprime: Term, Tprime: Term> Subst<J, S> for Lam<Tp, T>
+    // The body of each branch is under the binder of the payload
+    trait MapBranches<F, C: Index> {
+        type Res: BranchList;
+    }
+    impl<F, C: Index> MapBranches<F, C> for BranchNil {
+        type Res = BranchNil;
+    }
+    impl<F, C: Index, L: Label, T: Term, Tprime: Term, Tl: BranchList, Tlprime: BranchList>
+        MapBranches<F, C> for BranchCons<L, T, Tl>
     where
-        S: ShiftBy1<Res = Sprime>,
-        T: Subst<ISucc<J>, Sprime, Res = Tprime>,
+        T: TermMap<F, ISucc<C>, Res = Tprime>,
+        Tl: MapBranches<F, C, Res = Tlprime>,
     {
-        type Res = Lam<Tp, Tprime>;
+        type Res = BranchCons<L, Tprime, Tlprime>;
     }
 
-    impl<J: Index, S: Term, T1: Term, T1prime: Term, T2: Term, T2prime: Term> Subst<J, S>
-        for App<T1, T2>
+    impl<F, C: Index, T: Term, Tprime: Term, Bs: BranchList, Bsprime: BranchList> TermMap<F, C>
+        for Match<T, Bs>
     where
-        T1: Subst<J, S, Res = T1prime>,
-        T2: Subst<J, S, Res = T2prime>,
+        T: TermMap<F, C, Res = Tprime>,
+        Bs: MapBranches<F, C, Res = Bsprime>,
     {
-        type Res = App<T1prime, T2prime>;
+        type Res = Match<Tprime, Bsprime>;
+    }
+
+    impl<F, C: Index, Tp: Type> TermMap<F, C> for Nil<Tp> {
+        type Res = Nil<Tp>;
+    }
+    impl<F, C: Index> TermMap<F, C> for CNil {
+        type Res = CNil;
     }
 
-    impl<
-        J: Index,
-        S: Term,
-        T1: Term,
-        T1prime: Term,
-        T2: Term,
-        T2prime: Term,
-        T3: Term,
-        T3prime: Term,
-    > Subst<J, S> for If<T1, T2, T3>
+    impl<F, C: Index, H: Term, Hprime: Term, T: Term, Tprime: Term> TermMap<F, C> for Cons<H, T>
     where
-        T1: Subst<J, S, Res = T1prime>,
-        T2: Subst<J, S, Res = T2prime>,
-        T3: Subst<J, S, Res = T3prime>,
+        H: TermMap<F, C, Res = Hprime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = If<T1prime, T2prime, T3prime>;
+        type Res = Cons<Hprime, Tprime>;
     }
 
-    impl<J: Index, S: Term, Sprime: Term, T1: Term, T1prime: Term, T2: Term, T2prime: Term>
-        Subst<J, S> for Let<T1, T2>
+    impl<F, C: Index, L: Term, Lprime: Term, N: Term, Nprime: Term, B: Term, Bprime: Term>
+        TermMap<F, C> for ListCase<L, N, B>
     where
-        T1: Subst<J, S, Res = T1prime>,
-        S: ShiftBy1<Res = Sprime>,
-        T2: Subst<ISucc<J>, Sprime, Res = T2prime>,
+        L: TermMap<F, C, Res = Lprime>,
+        N: TermMap<F, C, Res = Nprime>,
+        B: TermMap<F, ISucc<ISucc<C>>, Res = Bprime>,
     {
-        type Res = Let<T1prime, T2prime>;
+        type Res = ListCase<Lprime, Nprime, Bprime>;
     }
 
-    impl<J: Index, S: Term, T: Term, Tprime: Term> Subst<J, S> for Fix<T>
+    impl<F, C: Index, H: Type, T: Term, Tprime: Term, ExTp: Type> TermMap<F, C> for Pack<H, T, ExTp>
     where
-        T: Subst<J, S, Res = Tprime>,
+        T: TermMap<F, C, Res = Tprime>,
     {
-        type Res = Fix<Tprime>;
+        type Res = Pack<H, Tprime, ExTp>;
+    }
+
+    impl<F, C: Index, T: Term, Tprime: Term, B: Term, Bprime: Term> TermMap<F, C> for Unpack<T, B>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+        B: TermMap<F, ISucc<C>, Res = Bprime>,
+    {
+        type Res = Unpack<Tprime, Bprime>;
+    }
+
+    impl<F, C: Index, T: Term, Tprime: Term, From: Type, To: Type> TermMap<F, C> for Cast<T, From, To>
+    where
+        T: TermMap<F, C, Res = Tprime>,
+    {
+        type Res = Cast<Tprime, From, To>;
+    }
+
+    impl<F, C: Index> TermMap<F, C> for Blame {
+        type Res = Blame;
     }
 }
 
-mod shiftbyneg1 {
+pub mod shiftby1 {
     use super::*;
+    use termmap::{MapVar, TermMap};
 
-    pub trait ShiftByNeg1 {
+    pub trait ShiftBy1 {
         type Res: Term;
     }
-    trait Shift<C: Index> {
+    impl<T: Term, R: Term> ShiftBy1 for T
+    where
+        T: TermMap<Up, I0, Res = R>,
+    {
+        type Res = R;
+    }
+
+    // Adds one to the variables which are free, that is at least the cutoff
+    pub struct Up;
+    impl<I: Index, C: Index, R: Index> MapVar<I, C> for Up
+    where
+        I: ShiftVar<C, Res = R>,
+    {
+        type Res = Var<R>;
+    }
+
+    trait ShiftVar<C: Index> {
+        type Res: Index;
+    }
+    impl<K: Index> ShiftVar<I0> for K {
+        type Res = ISucc<K>;
+    }
+    impl<N: Index> ShiftVar<ISucc<N>> for I0 {
+        type Res = I0;
+    }
+    impl<N: Index, K: Index, R: Index> ShiftVar<ISucc<N>> for ISucc<K>
+    where
+        K: ShiftVar<N, Res = R>,
+    {
+        type Res = ISucc<R>;
+    }
+}
+
+pub mod subst {
+    use super::*;
+    use crate::evaluator::shiftby1::ShiftBy1;
+    use termmap::{MapVar, TermMap};
+
+    pub trait Subst<J: Index, S: Term> {
         type Res: Term;
     }
-    impl<T: Term, R: Term> ShiftByNeg1 for T
+    impl<J: Index, S: Term, T: Term, R: Term> Subst<J, S> for T
     where
-        T: Shift<I0, Res = R>,
+        T: TermMap<Replace<J, S>, I0, Res = R>,
     {
         type Res = R;
     }
 
-    impl<C: Index> Shift<C> for True {
-        type Res = True;
+    // Replaces the variable J by S
+    pub struct Replace<J: Index, S: Term>(PhantomData<(J, S)>);
+    impl<J: Index, S: Term, I: Index, R: Term> MapVar<I, I0> for Replace<J, S>
+    where
+        (I, J): Select<S, Var<I>, Res = R>,
+    {
+        type Res = R;
     }
-    impl<C: Index> Shift<C> for False {
-        type Res = False;
+    // Under a binder, J + 1 is replaced by S shifted once
+    impl<J: Index, S: Term, I: Index, C: Index, Sprime: Term, R: Term> MapVar<I, ISucc<C>>
+        for Replace<J, S>
+    where
+        S: ShiftBy1<Res = Sprime>,
+        Replace<ISucc<J>, Sprime>: MapVar<I, C, Res = R>,
+    {
+        type Res = R;
     }
-    impl<C: Index> Shift<C> for Zero {
-        type Res = Zero;
+
+    trait Select<S: Term, K: Term> {
+        type Res: Term;
+    }
+    impl<S: Term, K: Term> Select<S, K> for (I0, I0) {
+        type Res = S;
+    }
+    impl<S: Term, K: Term, N: Index> Select<S, K> for (ISucc<N>, I0) {
+        type Res = K;
+    }
+    impl<S: Term, K: Term, N: Index> Select<S, K> for (I0, ISucc<N>) {
+        type Res = K;
     }
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for Succ<T>
+    impl<S: Term, K: Term, N1: Index, N2: Index, R: Term> Select<S, K> for (ISucc<N1>, ISucc<N2>)
     where
-        T: Shift<C, Res = Tprime>,
+        (N1, N2): Select<S, K, Res = R>,
     {
-        type Res = Succ<Tprime>;
+        type Res = R;
+    }
+}
+
+pub mod shiftbyneg1 {
+    use super::*;
+    use termmap::{MapVar, TermMap};
+
+    pub trait ShiftByNeg1 {
+        type Res: Term;
     }
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for Pred<T>
+    impl<T: Term, R: Term> ShiftByNeg1 for T
     where
-        T: Shift<C, Res = Tprime>,
+        T: TermMap<Down, I0, Res = R>,
     {
-        type Res = Pred<Tprime>;
+        type Res = R;
     }
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for IsZero<T>
+
+    // Subtracts one from the variables which are free, that is at least the
+    // cutoff. There is no rule for the cutoff itself, it is the variable
+    // which was just substituted away.
+    pub struct Down;
+    impl<I: Index, C: Index, R: Index> MapVar<I, C> for Down
     where
-        T: Shift<C, Res = Tprime>,
+        I: ShiftVar<C, Res = R>,
     {
-        type Res = IsZero<Tprime>;
+        type Res = Var<R>;
     }
 
     trait ShiftVar<C: Index> {
@@ -296,52 +506,96 @@ mod shiftbyneg1 {
     {
         type Res = ISucc<R>;
     }
+}
+
+// The three passes of a beta reduction fused into one, [0 -> V] substitutes
+// V for the variable 0 and lowers every other free variable by one. Only
+// closed values are substituted during evaluation, so unlike with Subst the
+// value does not need to be shifted under binders. With the three-pass
+// feature it is done with ShiftBy1, Subst and ShiftByNeg1 instead, to compare
+// the two.
+pub(crate) mod instantiate {
+    use super::*;
+
+    pub trait Instantiate<V: Term> {
+        type Res: Term;
+    }
 
-    impl<C: Index, I: Index, R: Index> Shift<C> for Var<I>
+    #[cfg(feature = "three-pass")]
+    impl<V: Term, T: Term, Vprime: Term, Tprime: Term, R: Term> Instantiate<V> for T
     where
-        I: ShiftVar<C, Res = R>,
+        V: shiftby1::ShiftBy1<Res = Vprime>,
+        T: subst::Subst<I0, Vprime, Res = Tprime>,
+        Tprime: shiftbyneg1::ShiftByNeg1<Res = R>,
     {
-        type Res = Var<R>;
+        type Res = R;
     }
 
-    impl<C: Index, Tp: Type, T: Term, R: Term> Shift<C> for Lam<Tp, T>
+    #[cfg(not(feature = "three-pass"))]
+    impl<V: Term, T: Term, R: Term> Instantiate<V> for T
     where
-        T: Shift<ISucc<C>, Res = R>,
+        T: termmap::TermMap<fused::With<V>, I0, Res = R>,
     {
-        type Res = Lam<Tp, R>;
+        type Res = R;
     }
+}
+
+#[cfg(not(feature = "three-pass"))]
+mod fused {
+    use super::*;
+    use termmap::MapVar;
 
-    impl<C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term> Shift<C> for App<T1, T2>
+    pub(crate) struct With<V: Term>(PhantomData<V>);
+    impl<V: Term, I: Index, C: Index, O, R: Term> MapVar<I, C> for With<V>
     where
-        T1: Shift<C, Res = T1prime>,
-        T2: Shift<C, Res = T2prime>,
+        I: Compare<C, Res = O>,
+        O: Outcome<V, Res = R>,
     {
-        type Res = App<T1prime, T2prime>;
+        type Res = R;
     }
 
-    impl<C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term, T3: Term, T3prime: Term>
-        Shift<C> for If<T1, T2, T3>
+    // Compares the variable to the cutoff, it is either the one substituted
+    // or it is kept, under the cutoff as it is and above it lowered by one
+    pub(crate) struct Hit;
+    pub(crate) struct Keep<K: Index>(PhantomData<K>);
+    trait Compare<C: Index> {
+        type Res;
+    }
+    impl Compare<I0> for I0 {
+        type Res = Hit;
+    }
+    impl<N: Index> Compare<I0> for ISucc<N> {
+        type Res = Keep<N>;
+    }
+    impl<M: Index> Compare<ISucc<M>> for I0 {
+        type Res = Keep<I0>;
+    }
+    impl<N: Index, M: Index, O, R> Compare<ISucc<M>> for ISucc<N>
     where
-        T1: Shift<C, Res = T1prime>,
-        T2: Shift<C, Res = T2prime>,
-        T3: Shift<C, Res = T3prime>,
+        N: Compare<M, Res = O>,
+        O: Lift<Res = R>,
     {
-        type Res = If<T1prime, T2prime, T3prime>;
+        type Res = R;
     }
 
-    impl<C: Index, T1: Term, T1prime: Term, T2: Term, T2prime: Term> Shift<C> for Let<T1, T2>
-    where
-        T1: Shift<C, Res = T1prime>,
-        T2: Shift<ISucc<C>, Res = T2prime>,
-    {
-        type Res = Let<T1prime, T2prime>;
+    trait Lift {
+        type Res;
+    }
+    impl Lift for Hit {
+        type Res = Hit;
+    }
+    impl<K: Index> Lift for Keep<K> {
+        type Res = Keep<ISucc<K>>;
     }
 
-    impl<C: Index, T: Term, Tprime: Term> Shift<C> for Fix<T>
-    where
-        T: Shift<C, Res = Tprime>,
-    {
-        type Res = Fix<Tprime>;
+    trait Outcome<V: Term> {
+        type Res: Term;
+    }
+    impl<V: Term> Outcome<V> for Hit {
+        type Res = V;
+    }
+    impl<V: Term, K: Index> Outcome<V> for Keep<K> {
+        type Res = Var<K>;
     }
 }
 
@@ -349,6 +603,26 @@ pub trait Eval {
     type Res: Value;
 }
 
+// Evaluation rules that evaluate a subterm first are written in a
+// continuation passing style: once the subterm is evaluated, its result is
+// passed to a continuation K with Bind<K>. This way a raised exception can
+// be propagated by Bind alone, without being handled in every rule.
+trait Kont<V: Value> {
+    type Res: Value;
+}
+trait Bind<K> {
+    type Res: Value;
+}
+impl<V: Plain, K: Kont<V>> Bind<K> for V {
+    type Res = <K as Kont<V>>::Res;
+}
+impl<V: Plain, K> Bind<K> for Raise<V> {
+    type Res = Raise<V>;
+}
+impl<K> Bind<K> for Blame {
+    type Res = Blame;
+}
+
 // E-True
 impl Eval for True {
     type Res = True;
@@ -359,64 +633,163 @@ impl Eval for False {
     type Res = False;
 }
 
+// E-Triv
+impl Eval for Triv {
+    type Res = Triv;
+}
+
 // E-Lam
 impl<Tp: Type, T: Term> Eval for Lam<Tp, T> {
     type Res = Lam<Tp, T>;
 }
 
-// E-App
-impl<
-    T1: Term,
-    T2: Term,
-    Tp: Type,
-    T1prime: Term,
-    V2: Value,
-    V2prime: Term,
-    T1primeprime: Term,
-    R: Term,
-    Rprime: Value,
-> Eval for App<T1, T2>
-where
-    T1: Eval<Res = Lam<Tp, T1prime>>,
-    T2: Eval<Res = V2>,
-    V2: ShiftBy1<Res = V2prime>,
-    T1prime: Subst<I0, V2prime, Res = T1primeprime>,
-    T1primeprime: ShiftByNeg1<Res = R>,
-    R: Eval<Res = Rprime>,
-{
-    type Res = Rprime;
+// Abstractions, typed, unannotated or untyped, with their body
+pub(crate) trait Abs: Value {
+    type Body: Term;
+}
+impl<Tp: Type, T: Term> Abs for Lam<Tp, T> {
+    type Body = T;
+}
+impl<T: Term> Abs for CLam<T> {
+    type Body = T;
+}
+impl<T: Term> Abs for ULam<T> {
+    type Body = T;
 }
 
-// E-Let
-impl<T1: Term, V1: Value, T2: Term, V1prime: Term, T2prime: Term, T2primeprime: Term, Rprime: Value>
-    Eval for Let<T1, T2>
+// E-CLam
+impl<T: Term> Eval for CLam<T> {
+    type Res = CLam<T>;
+}
+
+// E-ULam, the rules never look at types so untyped terms are evaluated
+// by the same Eval, sharing its substitution
+impl<T: Term> Eval for ULam<T> {
+    type Res = ULam<T>;
+}
+
+// E-Ann
+impl<T: Term, Tp: Type, V: Value> Eval for Ann<T, Tp>
 where
-    T1: Eval<Res = V1>,
-    V1: ShiftBy1<Res = V1prime>,
-    T2: Subst<I0, V1prime, Res = T2prime>,
-    T2prime: ShiftByNeg1<Res = T2primeprime>,
-    T2primeprime: Eval<Res = Rprime>,
+    T: Eval<Res = V>,
 {
-    type Res = Rprime;
+    type Res = V;
 }
 
-// E-Fix
-impl<T: Term, Tp: Type, T1: Term, R: Value> Eval for Fix<T>
+// Substitutes a value for index 0 in T, then evaluates it
+pub(crate) struct BetaK<T: Term>(PhantomData<T>);
+impl<T: Term, V: Value, Tprime: Term, R: Value> Kont<V> for BetaK<T>
 where
-    T: Eval<Res = Lam<Tp, T1>>,
-    T1: Subst<I0, Fix<Lam<Tp, T1>>, Res = R>,
+    T: Instantiate<V, Res = Tprime>,
+    Tprime: Eval<Res = R>,
 {
     type Res = R;
 }
 
-// For E-If we need to use a little trick,
-// because if we implement two rules E-True and E-False
-// then rustc will complain that there might be overallping
-// instances for Eval. Therefore we use a helper trait Select
-// wich given two terms selects the approriate one. For True
-// it selects the first and for False it selects the second.
-trait Select<T1: Term, T2: Term> {
-    type Res: Term;
+// E-App
+pub(crate) struct AppK<T: Term>(PhantomData<T>);
+impl<T2: Term, F: Abs, V2: Value, R: Value> Kont<F> for AppK<T2>
+where
+    T2: Eval<Res = V2>,
+    V2: Bind<BetaK<F::Body>, Res = R>,
+{
+    type Res = R;
+}
+// E-AppCast, applying a wrapped function casts the argument to the
+// type the function expects and the result back to the one promised.
+// A function projected from Dyn may be wrapped with types that are not
+// consistent, so both casts go through Dyn where a mismatch is blamed.
+impl<T2: Term, F: Plain, A: Type, B: Type, C: Type, D: Type, R: Value>
+    Kont<Cast<F, Arrow<A, B>, Arrow<C, D>>> for AppK<T2>
+where
+    Cast<Cast<App<F, Cast<Cast<T2, C, Dyn>, Dyn, A>>, B, Dyn>, Dyn, D>: Eval<Res = R>,
+{
+    type Res = R;
+}
+impl<T1: Term, T2: Term, V1: Value, R: Value> Eval for App<T1, T2>
+where
+    T1: Eval<Res = V1>,
+    V1: Bind<AppK<T2>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Let
+impl<T1: Term, T2: Term, V1: Value, R: Value> Eval for Let<T1, T2>
+where
+    T1: Eval<Res = V1>,
+    V1: Bind<BetaK<T2>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Fix
+pub(crate) struct FixK;
+impl<F: Abs, R: Value> Kont<F> for FixK
+where
+    F::Body: Instantiate<Fix<F>, Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, V: Value, R: Value> Eval for Fix<T>
+where
+    T: Eval<Res = V>,
+    V: Bind<FixK, Res = R>,
+{
+    type Res = R;
+}
+
+// For E-FixN, we take the body of the K-th function and substitute
+// each function of the group for its lambda, the same way E-Fix does.
+pub(crate) trait NthBody<K: Index> {
+    type Res: Term;
+}
+impl<Tp: Type, T: Term, Tl: FnList> NthBody<I0> for FnCons<Tp, T, Tl> {
+    type Res = T;
+}
+impl<K: Index, Tp: Type, T: Term, Tl: FnList, R: Term> NthBody<ISucc<K>> for FnCons<Tp, T, Tl>
+where
+    Tl: NthBody<K, Res = R>,
+{
+    type Res = R;
+}
+
+// Unfolds the function T, abstracted over the functions of the group
+// All starting from the J-th one, which are the remaining ones in Self.
+// The abstractions are typed, or untyped once erased.
+pub(crate) trait Unfold<T: Term, All: FnList, J: Index> {
+    type Res: Term;
+}
+impl<T: Term, All: FnList, J: Index> Unfold<T, All, J> for FnNil {
+    type Res = T;
+}
+impl<Tp: Type, F: Abs, T: Term, Tprime: Term, Tl: FnList, All: FnList, J: Index, R: Term>
+    Unfold<F, All, J> for FnCons<Tp, T, Tl>
+where
+    F::Body: Instantiate<FixN<All, J>, Res = Tprime>,
+    Tl: Unfold<Tprime, All, ISucc<J>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-FixN
+impl<Fs: FnList, K: Index, T: Term, T1: Term, R: Value> Eval for FixN<Fs, K>
+where
+    Fs: NthBody<K, Res = T>,
+    Fs: Unfold<T, Fs, I0, Res = T1>,
+    T1: Eval<Res = R>,
+{
+    type Res = R;
+}
+
+// For E-If we need to use a little trick,
+// because if we implement two rules E-True and E-False
+// then rustc will complain that there might be overallping
+// instances for Eval. Therefore we use a helper trait Select
+// wich given two terms selects the approriate one. For True
+// it selects the first and for False it selects the second.
+pub(crate) trait Select<T1: Term, T2: Term> {
+    type Res: Term;
 }
 impl<T1: Term, T2: Term> Select<T1, T2> for True {
     type Res = T1;
@@ -424,14 +797,21 @@ impl<T1: Term, T2: Term> Select<T1, T2> for True {
 impl<T1: Term, T2: Term> Select<T1, T2> for False {
     type Res = T2;
 }
-impl<Cond: Term, V: Value, T1: Term, T2: Term, T3: Term, R: Value> Eval for If<Cond, T1, T2>
+pub(crate) struct IfK<T1: Term, T2: Term>(PhantomData<(T1, T2)>);
+impl<T1: Term, T2: Term, V: Value, T3: Term, R: Value> Kont<V> for IfK<T1, T2>
 where
-    Cond: Eval<Res = V>,
     V: Select<T1, T2, Res = T3>,
     T3: Eval<Res = R>,
 {
     type Res = R;
 }
+impl<Cond: Term, V: Value, T1: Term, T2: Term, R: Value> Eval for If<Cond, T1, T2>
+where
+    Cond: Eval<Res = V>,
+    V: Bind<IfK<T1, T2>, Res = R>,
+{
+    type Res = R;
+}
 
 // // E-Zero
 impl Eval for Zero {
@@ -439,14 +819,19 @@ impl Eval for Zero {
 }
 
 // E-Succ
-impl<T: Term, V: Value> Eval for Succ<T>
+pub(crate) struct SuccK;
+impl<V: Plain> Kont<V> for SuccK {
+    type Res = Succ<V>;
+}
+impl<T: Term, V: Value, R: Value> Eval for Succ<T>
 where
     T: Eval<Res = V>,
+    V: Bind<SuccK, Res = R>,
 {
-    type Res = Succ<V>;
+    type Res = R;
 }
 
-trait IsZeroResult {
+pub(crate) trait IsZeroResult {
     type Res: Value;
 }
 impl IsZeroResult for Zero {
@@ -455,16 +840,23 @@ impl IsZeroResult for Zero {
 impl<T: Term> IsZeroResult for Succ<T> {
     type Res = False;
 }
+pub(crate) struct IsZeroK;
+impl<V: Value, R: Value> Kont<V> for IsZeroK
+where
+    V: IsZeroResult<Res = R>,
+{
+    type Res = R;
+}
 
 impl<T: Term, V: Value, R: Value> Eval for IsZero<T>
 where
     T: Eval<Res = V>,
-    V: IsZeroResult<Res = R>,
+    V: Bind<IsZeroK, Res = R>,
 {
     type Res = R;
 }
 
-trait PredResult {
+pub(crate) trait PredResult {
     type Res: Value;
 }
 impl PredResult for Zero {
@@ -473,12 +865,383 @@ impl PredResult for Zero {
 impl<V: Value> PredResult for Succ<V> {
     type Res = V;
 }
-impl<T: Term, V: Value, V2: Value> Eval for Pred<T>
+pub(crate) struct PredK;
+impl<V: Value, R: Value> Kont<V> for PredK
+where
+    V: PredResult<Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, V: Value, R: Value> Eval for Pred<T>
+where
+    T: Eval<Res = V>,
+    V: Bind<PredK, Res = R>,
+{
+    type Res = R;
+}
+
+// E-NatRec, once the number is a value we unfold one step of the recursion
+pub(crate) trait NatRecStep<Z: Term, S: Term> {
+    type Res: Term;
+}
+impl<Z: Term, S: Term> NatRecStep<Z, S> for Zero {
+    type Res = Z;
+}
+impl<Z: Term, S: Term, V: Value> NatRecStep<Z, S> for Succ<V> {
+    type Res = App<App<S, V>, NatRec<Z, S, V>>;
+}
+pub(crate) struct NatRecK<Z: Term, S: Term>(PhantomData<(Z, S)>);
+impl<Z: Term, S: Term, V: Value, T: Term, R: Value> Kont<V> for NatRecK<Z, S>
+where
+    V: NatRecStep<Z, S, Res = T>,
+    T: Eval<Res = R>,
+{
+    type Res = R;
+}
+impl<Z: Term, S: Term, N: Term, V: Value, R: Value> Eval for NatRec<Z, S, N>
+where
+    N: Eval<Res = V>,
+    V: Bind<NatRecK<Z, S>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Raise, the payload is evaluated before being raised
+pub(crate) struct RaiseK;
+impl<V: Plain> Kont<V> for RaiseK {
+    type Res = Raise<V>;
+}
+impl<T: Term, V: Value, R: Value> Eval for Raise<T>
+where
+    T: Eval<Res = V>,
+    V: Bind<RaiseK, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Try, if the body raises an exception its payload is
+// bound in the handler, otherwise its value is returned.
+trait Catch<H: Term> {
+    type Res: Value;
+}
+impl<H: Term, V: Plain> Catch<H> for V {
+    type Res = V;
+}
+impl<H: Term, V: Plain, R: Value> Catch<H> for Raise<V>
+where
+    BetaK<H>: Kont<V, Res = R>,
+{
+    type Res = R;
+}
+// Blame is not an exception, handlers cannot recover from it
+impl<H: Term> Catch<H> for Blame {
+    type Res = Blame;
+}
+impl<T: Term, H: Term, V: Value, R: Value> Eval for Try<T, H>
+where
+    T: Eval<Res = V>,
+    V: Catch<H, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Record, the fields are evaluated from left to right. Once a field is
+// evaluated, FieldK evaluates the remaining ones and ConsFieldK puts the
+// value back in front of the resulting record.
+trait EvalFields: FieldList {
+    type Res: Value;
+}
+impl EvalFields for FieldNil {
+    type Res = Record<FieldNil>;
+}
+impl<L: Label, T: Term, Tl: FieldList, V: Value, R: Value> EvalFields for FieldCons<L, T, Tl>
+where
+    T: Eval<Res = V>,
+    V: Bind<FieldK<L, Tl>, Res = R>,
+{
+    type Res = R;
+}
+pub(crate) struct FieldK<L: Label, Tl: FieldList>(PhantomData<(L, Tl)>);
+impl<L: Label, Tl: FieldList, V: Plain, Vs: Value, R: Value> Kont<V> for FieldK<L, Tl>
+where
+    Tl: EvalFields<Res = Vs>,
+    Vs: Bind<ConsFieldK<L, V>, Res = R>,
+{
+    type Res = R;
+}
+pub(crate) struct ConsFieldK<L: Label, V: Plain>(PhantomData<(L, V)>);
+impl<L: Label, V: Plain, Vs: FieldList + FieldValues> Kont<Record<Vs>> for ConsFieldK<L, V> {
+    type Res = Record<FieldCons<L, V, Vs>>;
+}
+impl<Fs: FieldList, R: Value> Eval for Record<Fs>
+where
+    Fs: EvalFields<Res = R>,
+{
+    type Res = R;
+}
+
+// E-Proj, the field is found the same way its type is found by T-Proj
+pub(crate) trait GetField<L: Label>: FieldList {
+    type Res: Value;
+}
+trait GetFieldIf<L: Label, V: Value, Tl: FieldList> {
+    type Res: Value;
+}
+impl<L: Label, V: Value, Tl: FieldList> GetFieldIf<L, V, Tl> for True {
+    type Res = V;
+}
+impl<L: Label, V: Value, Tl: FieldList, R: Value> GetFieldIf<L, V, Tl> for False
+where
+    Tl: GetField<L, Res = R>,
+{
+    type Res = R;
+}
+impl<L: Label, L1: Label, V: Value, Tl: FieldList, B: Term, R: Value> GetField<L>
+    for FieldCons<L1, V, Tl>
+where
+    L1: LabelEq<L, Res = B>,
+    B: GetFieldIf<L, V, Tl, Res = R>,
+{
+    type Res = R;
+}
+pub(crate) struct ProjK<L: Label>(PhantomData<L>);
+impl<L: Label, Vs: FieldList + FieldValues, R: Value> Kont<Record<Vs>> for ProjK<L>
+where
+    Vs: GetField<L, Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, L: Label, V: Value, R: Value> Eval for Proj<T, L>
+where
+    T: Eval<Res = V>,
+    V: Bind<ProjK<L>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Tag
+pub(crate) struct TagK<L: Label, Tp: Type>(PhantomData<(L, Tp)>);
+impl<L: Label, Tp: Type, V: Plain> Kont<V> for TagK<L, Tp> {
+    type Res = Tag<L, V, Tp>;
+}
+impl<L: Label, T: Term, Tp: Type, V: Value, R: Value> Eval for Tag<L, T, Tp>
+where
+    T: Eval<Res = V>,
+    V: Bind<TagK<L, Tp>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Match, the payload is substituted in the branch of its case
+pub(crate) struct MatchK<Bs: BranchList>(PhantomData<Bs>);
+impl<Bs: BranchList, L: Label, V: Plain, Tp: Type, B: Term, R: Value> Kont<Tag<L, V, Tp>>
+    for MatchK<Bs>
+where
+    Bs: Branch<L, Res = B>,
+    BetaK<B>: Kont<V, Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, Bs: BranchList, V: Value, R: Value> Eval for Match<T, Bs>
+where
+    T: Eval<Res = V>,
+    V: Bind<MatchK<Bs>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Nil
+impl<Tp: Type> Eval for Nil<Tp> {
+    type Res = Nil<Tp>;
+}
+impl Eval for CNil {
+    type Res = CNil;
+}
+
+// E-Cons, the head is evaluated before the tail
+pub(crate) struct ConsK<T: Term>(PhantomData<T>);
+pub(crate) struct ConsTailK<H: Plain>(PhantomData<H>);
+impl<T: Term, H: Plain, V: Value, R: Value> Kont<H> for ConsK<T>
+where
+    T: Eval<Res = V>,
+    V: Bind<ConsTailK<H>, Res = R>,
+{
+    type Res = R;
+}
+impl<H: Plain, V: Plain> Kont<V> for ConsTailK<H> {
+    type Res = Cons<H, V>;
+}
+impl<H: Term, T: Term, V: Value, R: Value> Eval for Cons<H, T>
+where
+    H: Eval<Res = V>,
+    V: Bind<ConsK<T>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-ListCase, the head and the tail are bound with Lets. Since both are
+// closed values, the tail does not need to be shifted under the first Let.
+pub(crate) trait ListStep<N: Term, B: Term> {
+    type Res: Term;
+}
+impl<N: Term, B: Term, Tp: Type> ListStep<N, B> for Nil<Tp> {
+    type Res = N;
+}
+impl<N: Term, B: Term> ListStep<N, B> for CNil {
+    type Res = N;
+}
+impl<N: Term, B: Term, H: Plain, T: Plain> ListStep<N, B> for Cons<H, T> {
+    type Res = Let<H, Let<T, B>>;
+}
+pub(crate) struct ListCaseK<N: Term, B: Term>(PhantomData<(N, B)>);
+impl<N: Term, B: Term, V: Value, T: Term, R: Value> Kont<V> for ListCaseK<N, B>
+where
+    V: ListStep<N, B, Res = T>,
+    T: Eval<Res = R>,
+{
+    type Res = R;
+}
+impl<L: Term, N: Term, B: Term, V: Value, R: Value> Eval for ListCase<L, N, B>
+where
+    L: Eval<Res = V>,
+    V: Bind<ListCaseK<N, B>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Pack
+pub(crate) struct PackK<H: Type, ExTp: Type>(PhantomData<(H, ExTp)>);
+impl<H: Type, ExTp: Type, V: Plain> Kont<V> for PackK<H, ExTp> {
+    type Res = Pack<H, V, ExTp>;
+}
+impl<H: Type, T: Term, ExTp: Type, V: Value, R: Value> Eval for Pack<H, T, ExTp>
+where
+    T: Eval<Res = V>,
+    V: Bind<PackK<H, ExTp>, Res = R>,
+{
+    type Res = R;
+}
+
+// E-UnpackPack, types play no role during evaluation so only the term of
+// the package is substituted, the type variable is left in the body
+pub(crate) struct UnpackK<B: Term>(PhantomData<B>);
+impl<B: Term, H: Type, V: Plain, ExTp: Type, R: Value> Kont<Pack<H, V, ExTp>> for UnpackK<B>
+where
+    BetaK<B>: Kont<V, Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, B: Term, V: Value, R: Value> Eval for Unpack<T, B>
+where
+    T: Eval<Res = V>,
+    V: Bind<UnpackK<B>, Res = R>,
+{
+    type Res = R;
+}
+
+// The types a value of type Dyn can be injected from. Functions are
+// injected with their own type rather than through Dyn -> Dyn, so
+// checking that they fit the type they are cast back to is deferred
+// until they are applied.
+pub(crate) trait Ground: Type {}
+impl Ground for Bool {}
+impl Ground for Nat {}
+impl Ground for Unit {}
+impl<A: Type, B: Type> Ground for Arrow<A, B> {}
+
+// E-Cast, once the term is a value the cast between the two types is
+// performed by CastK. A cast to Dyn injects the value, a cast between
+// function types wraps it, and a cast from Dyn projects an injected
+// value with Project, blaming it if it was injected from another type.
+pub(crate) struct CastK<From: Type, To: Type>(PhantomData<(From, To)>);
+impl<V: Value> Kont<V> for CastK<Bool, Bool> {
+    type Res = V;
+}
+impl<V: Value> Kont<V> for CastK<Nat, Nat> {
+    type Res = V;
+}
+impl<V: Value> Kont<V> for CastK<Unit, Unit> {
+    type Res = V;
+}
+impl<V: Value> Kont<V> for CastK<Dyn, Dyn> {
+    type Res = V;
+}
+impl<V: Plain, G: Ground> Kont<V> for CastK<G, Dyn> {
+    type Res = Cast<V, G, Dyn>;
+}
+impl<V: Plain, A: Type, B: Type, C: Type, D: Type> Kont<V> for CastK<Arrow<A, B>, Arrow<C, D>> {
+    type Res = Cast<V, Arrow<A, B>, Arrow<C, D>>;
+}
+impl<V: Plain, G: Ground, To: Ground, R: Value> Kont<Cast<V, G, Dyn>> for CastK<Dyn, To>
+where
+    G: Project<V, To, Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, From: Type, To: Type, V: Value, R: Value> Eval for Cast<T, From, To>
 where
     T: Eval<Res = V>,
-    V: PredResult<Res = V2>,
+    V: Bind<CastK<From, To>, Res = R>,
 {
-    type Res = V2;
+    type Res = R;
+}
+
+// Self is the type V was injected from
+pub(crate) trait Project<V: Value, To: Type> {
+    type Res: Value;
+}
+impl<V: Value> Project<V, Bool> for Bool {
+    type Res = V;
+}
+impl<V: Value> Project<V, Nat> for Bool {
+    type Res = Blame;
+}
+impl<V: Value> Project<V, Unit> for Bool {
+    type Res = Blame;
+}
+impl<V: Value, C: Type, D: Type> Project<V, Arrow<C, D>> for Bool {
+    type Res = Blame;
+}
+impl<V: Value> Project<V, Bool> for Nat {
+    type Res = Blame;
+}
+impl<V: Value> Project<V, Nat> for Nat {
+    type Res = V;
+}
+impl<V: Value> Project<V, Unit> for Nat {
+    type Res = Blame;
+}
+impl<V: Value, C: Type, D: Type> Project<V, Arrow<C, D>> for Nat {
+    type Res = Blame;
+}
+impl<V: Value> Project<V, Bool> for Unit {
+    type Res = Blame;
+}
+impl<V: Value> Project<V, Nat> for Unit {
+    type Res = Blame;
+}
+impl<V: Value> Project<V, Unit> for Unit {
+    type Res = V;
+}
+impl<V: Value, C: Type, D: Type> Project<V, Arrow<C, D>> for Unit {
+    type Res = Blame;
+}
+impl<V: Value, A: Type, B: Type> Project<V, Bool> for Arrow<A, B> {
+    type Res = Blame;
+}
+impl<V: Value, A: Type, B: Type> Project<V, Nat> for Arrow<A, B> {
+    type Res = Blame;
+}
+impl<V: Value, A: Type, B: Type> Project<V, Unit> for Arrow<A, B> {
+    type Res = Blame;
+}
+impl<V: Plain, A: Type, B: Type, C: Type, D: Type> Project<V, Arrow<C, D>> for Arrow<A, B> {
+    type Res = Cast<V, Arrow<A, B>, Arrow<C, D>>;
+}
+
+// E-Blame
+impl Eval for Blame {
+    type Res = Blame;
 }
 
 pub fn eval_to<T, V>()
@@ -492,3 +1255,59 @@ where
 pub fn eval<T: Eval>() -> PhantomData<T::Res> {
     PhantomData
 }
+
+/// V: EvalOf<T> holds when T evaluates to V. stlc_eval! in verify mode
+/// emits `<V as EvalOf<T>>::Res`, which rustc can only normalize to V
+/// after running the trait-based evaluator on T.
+pub trait EvalOf<T: Term>: Value {
+    type Res: Value;
+}
+impl<T: Term, V: Value> EvalOf<T> for V
+where
+    T: Eval<Res = V>,
+{
+    type Res = V;
+}
+
+// A substitution for the free variables of a term, given as a list of
+// closed values where the head is the value of index 0.
+pub trait ValEnv {}
+pub struct EmptyVals;
+impl ValEnv for EmptyVals {}
+pub struct ValCons<V: Value, Tl: ValEnv>(PhantomData<(V, Tl)>);
+impl<V: Value, Tl: ValEnv> ValEnv for ValCons<V, Tl> {}
+
+// Closes T under a substitution by binding each value with a Let,
+// so that the regular E-Let rule performs the substitution.
+pub trait Close<T: Term> {
+    type Res: Term;
+}
+impl<T: Term> Close<T> for EmptyVals {
+    type Res = T;
+}
+impl<T: Term, V: Value, Tl: ValEnv, R: Term> Close<T> for ValCons<V, Tl>
+where
+    Tl: Close<Let<V, T>, Res = R>,
+{
+    type Res = R;
+}
+
+pub fn eval_to_in<S, T, V>()
+where
+    S: ValEnv,
+    S: Close<T>,
+    V: Value,
+    T: Term,
+    <S as Close<T>>::Res: Eval<Res = V>,
+{
+}
+
+pub fn eval_in<S, T>() -> PhantomData<<<S as Close<T>>::Res as Eval>::Res>
+where
+    S: ValEnv,
+    S: Close<T>,
+    T: Term,
+    <S as Close<T>>::Res: Eval,
+{
+    PhantomData
+}
@@ -0,0 +1,275 @@
+use crate::{
+    evaluator::{Eval, Value},
+    term::*,
+    typechecker::{Checks, ElseSide, Side, ThenSide, WellTyped},
+};
+use std::marker::PhantomData;
+
+// Self: Consistent<U> if the two types agree wherever neither of them is
+// Dyn. Consistency replaces type equality in the gradual typing rules, it is
+// reflexive and symmetric but not transitive, since otherwise every type
+// would be consistent with every other one through Dyn.
+pub trait Consistent<U: Type>: Type {}
+
+// C-Refl, for the base types
+impl Consistent<Bool> for Bool {}
+impl Consistent<Nat> for Nat {}
+impl Consistent<Unit> for Unit {}
+impl Consistent<Dyn> for Dyn {}
+
+// C-DynR and C-DynL, written once per type constructor as the two blanket
+// impls would overlap on Dyn ~ Dyn
+impl Consistent<Dyn> for Bool {}
+impl Consistent<Dyn> for Nat {}
+impl Consistent<Dyn> for Unit {}
+impl<A: Type, B: Type> Consistent<Dyn> for Arrow<A, B> {}
+impl Consistent<Bool> for Dyn {}
+impl Consistent<Nat> for Dyn {}
+impl Consistent<Unit> for Dyn {}
+impl<A: Type, B: Type> Consistent<Arrow<A, B>> for Dyn {}
+
+// C-Arrow
+impl<A1: Type, B1: Type, A2: Type, B2: Type> Consistent<Arrow<A2, B2>> for Arrow<A1, B1>
+where
+    A1: Consistent<A2>,
+    B1: Consistent<B2>,
+{
+}
+
+// The type of a term in function position, seen as a function type. A term
+// of type Dyn may be applied, as a function from Dyn to Dyn.
+trait Fun: Type {
+    type Dom: Type;
+    type Cod: Type;
+}
+impl<A: Type, B: Type> Fun for Arrow<A, B> {
+    type Dom = A;
+    type Cod = B;
+}
+impl Fun for Dyn {
+    type Dom = Dyn;
+    type Cod = Dyn;
+}
+
+// The cast insertion translation, from the gradually typed pure fragment of
+// the language to the cast calculus. It follows the gradual typing rules, Tp
+// is the type of the term and Res the translated term, where a cast is
+// inserted wherever a type is only consistent with the expected one. Casts
+// are inserted even between equal types, where they do nothing at runtime.
+pub trait CastInsert<E: Env> {
+    type Tp: Type;
+    type Res: Term;
+}
+
+// T-True
+impl<E: Env> CastInsert<E> for True {
+    type Tp = Bool;
+    type Res = True;
+}
+
+// T-False
+impl<E: Env> CastInsert<E> for False {
+    type Tp = Bool;
+    type Res = False;
+}
+
+// T-Zero
+impl<E: Env> CastInsert<E> for Zero {
+    type Tp = Nat;
+    type Res = Zero;
+}
+
+// T-Unit
+impl<E: Env> CastInsert<E> for Triv {
+    type Tp = Unit;
+    type Res = Triv;
+}
+
+// T-Succ
+impl<E: Env, T: Term, Tp: Type, Tprime: Term> CastInsert<E> for Succ<T>
+where
+    T: CastInsert<E, Tp = Tp, Res = Tprime>,
+    Tp: Consistent<Nat>,
+{
+    type Tp = Nat;
+    type Res = Succ<Cast<Tprime, Tp, Nat>>;
+}
+
+// T-IsZero
+impl<E: Env, T: Term, Tp: Type, Tprime: Term> CastInsert<E> for IsZero<T>
+where
+    T: CastInsert<E, Tp = Tp, Res = Tprime>,
+    Tp: Consistent<Nat>,
+{
+    type Tp = Bool;
+    type Res = IsZero<Cast<Tprime, Tp, Nat>>;
+}
+
+// T-Pred
+impl<E: Env, T: Term, Tp: Type, Tprime: Term> CastInsert<E> for Pred<T>
+where
+    T: CastInsert<E, Tp = Tp, Res = Tprime>,
+    Tp: Consistent<Nat>,
+{
+    type Tp = Nat;
+    type Res = Pred<Cast<Tprime, Tp, Nat>>;
+}
+
+// T-Var
+impl<E: Env, I: Index, Tp: Type> CastInsert<E> for Var<I>
+where
+    Var<I>: WellTyped<E, Tp = Tp>,
+{
+    type Tp = Tp;
+    type Res = Var<I>;
+}
+
+// T-Lambda
+impl<E: Env, Tp: Type, T: Term, Tprime: Term> CastInsert<E> for Lam<Tp, T>
+where
+    T: CastInsert<TyCons<Tp, E>, Res = Tprime>,
+{
+    type Tp = Arrow<Tp, <T as CastInsert<TyCons<Tp, E>>>::Tp>;
+    type Res = Lam<Tp, Tprime>;
+}
+
+// A lambda without annotation has a parameter of type Dyn
+impl<E: Env, T: Term, Tprime: Term> CastInsert<E> for CLam<T>
+where
+    T: CastInsert<TyCons<Dyn, E>, Res = Tprime>,
+{
+    type Tp = Arrow<Dyn, <T as CastInsert<TyCons<Dyn, E>>>::Tp>;
+    type Res = Lam<Dyn, Tprime>;
+}
+
+// T-App, the function is cast to a function type and the argument to the
+// type of its parameter
+impl<
+    E: Env,
+    F: Term,
+    T: Term,
+    FTp: Type,
+    Dom: Type,
+    Cod: Type,
+    ATp: Type,
+    Fprime: Term,
+    Tprime: Term,
+> CastInsert<E> for App<F, T>
+where
+    F: CastInsert<E, Tp = FTp, Res = Fprime>,
+    T: CastInsert<E, Tp = ATp, Res = Tprime>,
+    FTp: Fun<Dom = Dom, Cod = Cod>,
+    ATp: Consistent<Dom>,
+{
+    type Tp = Cod;
+    type Res = App<Cast<Fprime, FTp, Arrow<Dom, Cod>>, Cast<Tprime, ATp, Dom>>;
+}
+
+// T-If, the else branch is cast to the type of the then branch
+impl<
+    E: Env,
+    Cond: Term,
+    Then: Term,
+    Else: Term,
+    CTp: Type,
+    Tp: Type,
+    ETp: Type,
+    Cprime: Term,
+    Tprime: Term,
+    Eprime: Term,
+> CastInsert<E> for If<Cond, Then, Else>
+where
+    Cond: CastInsert<E, Tp = CTp, Res = Cprime>,
+    Then: CastInsert<E, Tp = Tp, Res = Tprime>,
+    Else: CastInsert<E, Tp = ETp, Res = Eprime>,
+    CTp: Consistent<Bool>,
+    ETp: Consistent<Tp>,
+{
+    type Tp = Tp;
+    type Res = If<Cast<Cprime, CTp, Bool>, Tprime, Cast<Eprime, ETp, Tp>>;
+}
+
+// T-Let
+impl<E: Env, T: Term, Body: Term, Tp: Type, Tprime: Term, Bprime: Term> CastInsert<E>
+    for Let<T, Body>
+where
+    T: CastInsert<E, Tp = Tp, Res = Tprime>,
+    Body: CastInsert<TyCons<Tp, E>, Res = Bprime>,
+{
+    type Tp = <Body as CastInsert<TyCons<Tp, E>>>::Tp;
+    type Res = Let<Tprime, Bprime>;
+}
+
+// T-Fix, the function must be exactly of type Tp -> Tp since E-Fix only
+// unfolds abstractions and not wrapped functions
+impl<E: Env, T: Term, Tp: Type, Tprime: Term> CastInsert<E> for Fix<T>
+where
+    T: CastInsert<E, Tp = Arrow<Tp, Tp>, Res = Tprime>,
+{
+    type Tp = Tp;
+    type Res = Fix<Tprime>;
+}
+
+// T-Ann, an ascription casts the term to the given type
+impl<E: Env, T: Term, Tp: Type, S: Type, Tprime: Term> CastInsert<E> for Ann<T, Tp>
+where
+    T: CastInsert<E, Tp = S, Res = Tprime>,
+    S: Consistent<Tp>,
+{
+    type Tp = Tp;
+    type Res = Cast<Tprime, S, Tp>;
+}
+
+// T-Cast, a term of the cast calculus is well typed if its casts are
+// between consistent types, which is what cast insertion produces
+impl<E: Env, St: Env, T: Term, From: Type, To: Type> WellTyped<E, St> for Cast<T, From, To>
+where
+    T: WellTyped<E, St, Tp = From>,
+    From: Consistent<To>,
+{
+    type Tp = To;
+}
+impl<E: Env, St: Env, T: Term, From: Type, To: Type> Checks<E, To, St> for Cast<T, From, To> where
+    Cast<T, From, To>: WellTyped<E, St, Tp = To>
+{
+}
+
+// C-Blame, like Raise it can have any type
+impl<E: Env, St: Env, Tp: Type> Checks<E, Tp, St> for Blame {}
+impl Side for Blame {
+    type Res = ElseSide;
+}
+impl<T: Term, From: Type, To: Type> Side for Cast<T, From, To> {
+    type Res = ThenSide;
+}
+
+/// Util function to check that S is consistent with T
+pub fn is_consistent<S: Consistent<T>, T: Type>() {}
+
+/// Util function to type-check a closed gradually typed term
+pub fn gradual_checks<T>()
+where
+    T: Term,
+    T: CastInsert<EmptyEnv>,
+{
+}
+
+/// Util function to translate a closed gradually typed term to the cast calculus
+pub fn cast_insert<T>() -> PhantomData<<T as CastInsert<EmptyEnv>>::Res>
+where
+    T: Term,
+    T: CastInsert<EmptyEnv>,
+{
+    PhantomData
+}
+
+/// Util function to check that a closed gradually typed term evaluates to V,
+/// which is Blame if one of its casts fails
+pub fn gradual_eval_to<T, V>()
+where
+    T: Term,
+    V: Value,
+    T: CastInsert<EmptyEnv>,
+    <T as CastInsert<EmptyEnv>>::Res: Eval<Res = V>,
+{
+}
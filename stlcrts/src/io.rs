@@ -0,0 +1,244 @@
+use crate::{
+    evaluator::{Plain, Value},
+    store::*,
+    term::*,
+};
+use std::{fmt, marker::PhantomData};
+
+// The output of a program, the list of the values it printed in order
+pub trait Log {}
+pub struct EmptyLog;
+impl Log for EmptyLog {}
+pub struct LogCons<V: Value, Tl: Log>(PhantomData<(V, Tl)>);
+impl<V: Value, Tl: Log> Log for LogCons<V, Tl> {}
+
+// Appends V at the end of the log
+pub trait Append<V: Value>: Log {
+    type Res: Log;
+}
+impl<V: Value> Append<V> for EmptyLog {
+    type Res = LogCons<V, EmptyLog>;
+}
+impl<V: Value, W: Value, Tl: Log, R: Log> Append<V> for LogCons<W, Tl>
+where
+    Tl: Append<V, Res = R>,
+{
+    type Res = LogCons<W, R>;
+}
+
+// A store that also records the output of the program, so that the
+// store-passing evaluator can be reused to thread the log.
+pub struct Io<S: Store, L: Log>(PhantomData<(S, L)>);
+impl<S: Store, L: Log> Store for Io<S, L> {}
+impl<S: Store, L: Log, V: Value, I: Index, S1: Store> Alloc<V> for Io<S, L>
+where
+    S: Alloc<V, Loc = I, Res = S1>,
+{
+    type Loc = I;
+    type Res = Io<S1, L>;
+}
+impl<S: Store, L: Log, I: Index, V: Value> Lookup<I> for Io<S, L>
+where
+    S: Lookup<I, Res = V>,
+{
+    type Res = V;
+}
+impl<S: Store, L: Log, I: Index, V: Value, S1: Store> Update<I, V> for Io<S, L>
+where
+    S: Update<I, V, Res = S1>,
+{
+    type Res = Io<S1, L>;
+}
+
+// Stores that can record printed values
+pub trait Output<V: Value>: Store {
+    type Res: Store;
+}
+impl<S: Store, L: Log, V: Value, L1: Log> Output<V> for Io<S, L>
+where
+    L: Append<V, Res = L1>,
+{
+    type Res = Io<S, L1>;
+}
+
+// E-Print
+struct PrintK;
+impl<S: Store, V: Value, S1: Store> StKont<V, S> for PrintK
+where
+    S: Output<V, Res = S1>,
+{
+    type Res = Triv;
+    type Out = S1;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Print<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<PrintK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+/// A value brought back from the type level, so that it can be displayed at runtime
+#[derive(Clone, PartialEq, Eq)]
+pub enum RuntimeValue {
+    Unit,
+    Bool(bool),
+    Nat(u64),
+    Fun,
+    Loc(usize),
+    Raised(Box<RuntimeValue>),
+}
+
+impl fmt::Display for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeValue::Unit => write!(f, "()"),
+            RuntimeValue::Bool(b) => write!(f, "{b}"),
+            RuntimeValue::Nat(n) => write!(f, "{n}"),
+            RuntimeValue::Fun => write!(f, "<fun>"),
+            RuntimeValue::Loc(l) => write!(f, "<loc {l}>"),
+            RuntimeValue::Raised(v) => write!(f, "raise {v}"),
+        }
+    }
+}
+
+// Shown the same way as with Display, so that a Vec of them reads [1, 2]
+impl fmt::Debug for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// Only values of base types, and exceptions raising one, are brought back.
+// A program returning a record or a list is checked with eval_io_to instead.
+pub trait Reify: Value {
+    fn reify() -> RuntimeValue;
+}
+
+trait ReifyNat {
+    fn reify_nat() -> u64;
+}
+impl ReifyNat for Zero {
+    fn reify_nat() -> u64 {
+        0
+    }
+}
+impl<N: Value + ReifyNat> ReifyNat for Succ<N> {
+    fn reify_nat() -> u64 {
+        N::reify_nat() + 1
+    }
+}
+
+trait ReifyIndex {
+    fn reify_index() -> usize;
+}
+impl ReifyIndex for I0 {
+    fn reify_index() -> usize {
+        0
+    }
+}
+impl<I: ReifyIndex + Index> ReifyIndex for ISucc<I> {
+    fn reify_index() -> usize {
+        I::reify_index() + 1
+    }
+}
+
+impl Reify for Triv {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Unit
+    }
+}
+impl Reify for True {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Bool(true)
+    }
+}
+impl Reify for False {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Bool(false)
+    }
+}
+impl Reify for Zero {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Nat(0)
+    }
+}
+impl<N: Plain + ReifyNat> Reify for Succ<N> {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Nat(Self::reify_nat())
+    }
+}
+impl<Tp: Type, T: Term> Reify for Lam<Tp, T> {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Fun
+    }
+}
+impl<T: Term> Reify for CLam<T> {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Fun
+    }
+}
+impl<I: Index + ReifyIndex> Reify for Loc<I> {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Loc(I::reify_index())
+    }
+}
+impl<V: Plain + Reify> Reify for Raise<V> {
+    fn reify() -> RuntimeValue {
+        RuntimeValue::Raised(Box::new(V::reify()))
+    }
+}
+
+pub trait ReifyLog: Log {
+    fn reify() -> Vec<RuntimeValue>;
+}
+impl ReifyLog for EmptyLog {
+    fn reify() -> Vec<RuntimeValue> {
+        Vec::new()
+    }
+}
+impl<V: Reify, Tl: ReifyLog> ReifyLog for LogCons<V, Tl> {
+    fn reify() -> Vec<RuntimeValue> {
+        let mut log = vec![V::reify()];
+        log.extend(Tl::reify());
+        log
+    }
+}
+
+// Gives access to the log of the final store
+pub trait IoOut: Store {
+    type Log: Log;
+}
+impl<S: Store, L: Log> IoOut for Io<S, L> {
+    type Log = L;
+}
+
+type IoRes<T> = <T as EvalSt<Io<EmptyStore, EmptyLog>>>::Res;
+type IoLog<T> = <<T as EvalSt<Io<EmptyStore, EmptyLog>>>::Out as IoOut>::Log;
+
+/// Evaluates T while recording what it prints, returns the printed values and the result
+pub fn eval_io<T>() -> (Vec<RuntimeValue>, RuntimeValue)
+where
+    T: Term,
+    T: EvalSt<Io<EmptyStore, EmptyLog>>,
+    <T as EvalSt<Io<EmptyStore, EmptyLog>>>::Out: IoOut,
+    IoRes<T>: Reify,
+    IoLog<T>: ReifyLog,
+{
+    (
+        <IoLog<T> as ReifyLog>::reify(),
+        <IoRes<T> as Reify>::reify(),
+    )
+}
+
+/// Same as `eval_io` but checks the log and the result at compile time
+pub fn eval_io_to<T, L, V>()
+where
+    T: Term,
+    L: Log,
+    V: Value,
+    T: EvalSt<Io<EmptyStore, EmptyLog>, Res = V>,
+    <T as EvalSt<Io<EmptyStore, EmptyLog>>>::Out: IoOut<Log = L>,
+{
+}
@@ -1,8 +1,23 @@
 pub mod evaluator;
+pub mod gradual;
+pub mod io;
+pub mod machine;
+pub mod named;
+pub mod prelude;
+pub mod store;
+pub mod subtyping;
 pub mod term;
 pub mod typechecker;
+pub mod untyped;
 
 // Re-export the main public API
 pub use evaluator::*;
+pub use gradual::*;
+pub use io::*;
+pub use machine::*;
+pub use named::*;
+pub use store::*;
+pub use subtyping::*;
 pub use term::*;
 pub use typechecker::*;
+pub use untyped::*;
@@ -0,0 +1,565 @@
+use crate::{
+    evaluator::{
+        Abs, FieldValues, GetField, Ground, IsZeroResult, ListStep, NatRecStep, NthBody, Plain,
+        PredResult, Project, Select, Unfold, Value, instantiate::Instantiate,
+    },
+    term::*,
+};
+use std::marker::PhantomData;
+
+// An abstract machine in the style of the CK machine, where the evaluation
+// context is made explicit as a stack of frames. Having the context at hand
+// is what makes it possible to capture it with CallCC.
+//
+// The machine alternates between two modes, T: Run<K> evaluates the term T
+// under the stack K, and K: Return<V> passes the value V to the top frame.
+
+pub trait Run<K: Stack> {
+    type Res: Value;
+}
+pub trait Return<V: Value>: Stack {
+    type Res: Value;
+}
+
+// Substitutes V for index 0 in the body of a binder
+trait Beta<V: Term> {
+    type Res: Term;
+}
+impl<T: Term, V: Term, R: Term> Beta<V> for T
+where
+    T: Instantiate<V, Res = R>,
+{
+    type Res = R;
+}
+
+// The frames, each one is a term with a hole waiting for a value
+pub struct AppL<T: Term>(PhantomData<T>);
+pub struct AppR<F: Value>(PhantomData<F>);
+pub struct IfF<T1: Term, T2: Term>(PhantomData<(T1, T2)>);
+pub struct LetF<Body: Term>(PhantomData<Body>);
+pub struct SuccF;
+pub struct PredF;
+pub struct IsZeroF;
+pub struct FixF;
+pub struct NatRecF<Z: Term, S: Term>(PhantomData<(Z, S)>);
+pub struct RaiseF;
+pub struct TryF<H: Term>(PhantomData<H>);
+pub struct CallCCF;
+pub struct ThrowL<V: Term>(PhantomData<V>);
+pub struct ThrowR<K: Stack>(PhantomData<K>);
+pub struct FieldF<L: Label, Tl: FieldList>(PhantomData<(L, Tl)>);
+pub struct ConsFieldF<L: Label, V: Value>(PhantomData<(L, V)>);
+pub struct ProjF<L: Label>(PhantomData<L>);
+pub struct TagF<L: Label, Tp: Type>(PhantomData<(L, Tp)>);
+pub struct MatchF<Bs: BranchList>(PhantomData<Bs>);
+pub struct ConsF<T: Term>(PhantomData<T>);
+pub struct ConsTailF<H: Value>(PhantomData<H>);
+pub struct ListCaseF<N: Term, B: Term>(PhantomData<(N, B)>);
+pub struct PackF<H: Type, ExTp: Type>(PhantomData<(H, ExTp)>);
+pub struct UnpackF<B: Term>(PhantomData<B>);
+pub struct CastF<From: Type, To: Type>(PhantomData<(From, To)>);
+impl<T: Term> Frame for AppL<T> {}
+impl<F: Value> Frame for AppR<F> {}
+impl<T1: Term, T2: Term> Frame for IfF<T1, T2> {}
+impl<Body: Term> Frame for LetF<Body> {}
+impl Frame for SuccF {}
+impl Frame for PredF {}
+impl Frame for IsZeroF {}
+impl Frame for FixF {}
+impl<Z: Term, S: Term> Frame for NatRecF<Z, S> {}
+impl Frame for RaiseF {}
+impl<H: Term> Frame for TryF<H> {}
+impl Frame for CallCCF {}
+impl<V: Term> Frame for ThrowL<V> {}
+impl<K: Stack> Frame for ThrowR<K> {}
+impl<L: Label, Tl: FieldList> Frame for FieldF<L, Tl> {}
+impl<L: Label, V: Value> Frame for ConsFieldF<L, V> {}
+impl<L: Label> Frame for ProjF<L> {}
+impl<L: Label, Tp: Type> Frame for TagF<L, Tp> {}
+impl<Bs: BranchList> Frame for MatchF<Bs> {}
+impl<T: Term> Frame for ConsF<T> {}
+impl<H: Value> Frame for ConsTailF<H> {}
+impl<N: Term, B: Term> Frame for ListCaseF<N, B> {}
+impl<H: Type, ExTp: Type> Frame for PackF<H, ExTp> {}
+impl<B: Term> Frame for UnpackF<B> {}
+impl<From: Type, To: Type> Frame for CastF<From, To> {}
+
+// Values are returned to the stack
+impl<K: Return<True>> Run<K> for True {
+    type Res = K::Res;
+}
+impl<K: Return<False>> Run<K> for False {
+    type Res = K::Res;
+}
+impl<K: Return<Zero>> Run<K> for Zero {
+    type Res = K::Res;
+}
+impl<K: Return<Triv>> Run<K> for Triv {
+    type Res = K::Res;
+}
+impl<K: Return<Lam<Tp, T>>, Tp: Type, T: Term> Run<K> for Lam<Tp, T> {
+    type Res = K::Res;
+}
+impl<K: Return<CLam<T>>, T: Term> Run<K> for CLam<T> {
+    type Res = K::Res;
+}
+impl<K: Return<ULam<T>>, T: Term> Run<K> for ULam<T> {
+    type Res = K::Res;
+}
+impl<K: Return<ContV<K1>>, K1: Stack> Run<K> for ContV<K1> {
+    type Res = K::Res;
+}
+impl<K: Return<Nil<Tp>>, Tp: Type> Run<K> for Nil<Tp> {
+    type Res = K::Res;
+}
+impl<K: Return<CNil>> Run<K> for CNil {
+    type Res = K::Res;
+}
+
+// Other terms push a frame and evaluate their first subterm
+impl<K: Stack, T: Term, Tp: Type, R: Value> Run<K> for Ann<T, Tp>
+where
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T1: Term, T2: Term, R: Value> Run<K> for App<T1, T2>
+where
+    T1: Run<Push<AppL<T2>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, C: Term, T1: Term, T2: Term, R: Value> Run<K> for If<C, T1, T2>
+where
+    C: Run<Push<IfF<T1, T2>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, Body: Term, R: Value> Run<K> for Let<T, Body>
+where
+    T: Run<Push<LetF<Body>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, R: Value> Run<K> for Succ<T>
+where
+    T: Run<Push<SuccF, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, R: Value> Run<K> for Pred<T>
+where
+    T: Run<Push<PredF, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, R: Value> Run<K> for IsZero<T>
+where
+    T: Run<Push<IsZeroF, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, R: Value> Run<K> for Fix<T>
+where
+    T: Run<Push<FixF, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, Fs: FnList, I: Index, T: Term, T1: Term, R: Value> Run<K> for FixN<Fs, I>
+where
+    Fs: NthBody<I, Res = T>,
+    Fs: Unfold<T, Fs, I0, Res = T1>,
+    T1: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, Z: Term, S: Term, N: Term, R: Value> Run<K> for NatRec<Z, S, N>
+where
+    N: Run<Push<NatRecF<Z, S>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, R: Value> Run<K> for Raise<T>
+where
+    T: Run<Push<RaiseF, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, H: Term, R: Value> Run<K> for Try<T, H>
+where
+    T: Run<Push<TryF<H>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, R: Value> Run<K> for CallCC<T>
+where
+    T: Run<Push<CallCCF, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, K1: Term, V: Term, R: Value> Run<K> for Throw<K1, V>
+where
+    K1: Run<Push<ThrowL<V>, K>, Res = R>,
+{
+    type Res = R;
+}
+// The fields of a record are evaluated from left to right, the first one
+// is evaluated under a frame holding the remaining ones
+impl<K: Return<Record<FieldNil>>> Run<K> for Record<FieldNil> {
+    type Res = K::Res;
+}
+impl<K: Stack, L: Label, T: Term, Tl: FieldList, R: Value> Run<K> for Record<FieldCons<L, T, Tl>>
+where
+    T: Run<Push<FieldF<L, Tl>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, L: Label, R: Value> Run<K> for Proj<T, L>
+where
+    T: Run<Push<ProjF<L>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, L: Label, T: Term, Tp: Type, R: Value> Run<K> for Tag<L, T, Tp>
+where
+    T: Run<Push<TagF<L, Tp>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, Bs: BranchList, R: Value> Run<K> for Match<T, Bs>
+where
+    T: Run<Push<MatchF<Bs>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, H: Term, T: Term, R: Value> Run<K> for Cons<H, T>
+where
+    H: Run<Push<ConsF<T>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, L: Term, N: Term, B: Term, R: Value> Run<K> for ListCase<L, N, B>
+where
+    L: Run<Push<ListCaseF<N, B>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, H: Type, T: Term, ExTp: Type, R: Value> Run<K> for Pack<H, T, ExTp>
+where
+    T: Run<Push<PackF<H, ExTp>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, B: Term, R: Value> Run<K> for Unpack<T, B>
+where
+    T: Run<Push<UnpackF<B>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T: Term, From: Type, To: Type, R: Value> Run<K> for Cast<T, From, To>
+where
+    T: Run<Push<CastF<From, To>, K>, Res = R>,
+{
+    type Res = R;
+}
+// A failed cast discards the stack, handlers cannot recover from it
+impl<K: Stack> Run<K> for Blame {
+    type Res = Blame;
+}
+
+// The empty stack, the value is the result of the whole program
+impl<V: Value> Return<V> for Halt {
+    type Res = V;
+}
+
+// Once the function is a value, evaluate the argument
+impl<K: Stack, T: Term, F: Abs, R: Value> Return<F> for Push<AppL<T>, K>
+where
+    T: Run<Push<AppR<F>, K>, Res = R>,
+{
+    type Res = R;
+}
+// A wrapped function is applied as with Eval, by running the casts of the
+// argument and of the result around the function it wraps
+impl<K: Stack, T2: Term, F: Plain, A: Type, B: Type, C: Type, D: Type, R: Value>
+    Return<Cast<F, Arrow<A, B>, Arrow<C, D>>> for Push<AppL<T2>, K>
+where
+    Cast<Cast<App<F, Cast<Cast<T2, C, Dyn>, Dyn, A>>, B, Dyn>, Dyn, D>: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, F: Abs, V: Value, T: Term, R: Value> Return<V> for Push<AppR<F>, K>
+where
+    F::Body: Beta<V, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, T1: Term, T2: Term, V: Value, T: Term, R: Value> Return<V> for Push<IfF<T1, T2>, K>
+where
+    V: Select<T1, T2, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, Body: Term, V: Value, T: Term, R: Value> Return<V> for Push<LetF<Body>, K>
+where
+    Body: Beta<V, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, R: Value> Return<V> for Push<SuccF, K>
+where
+    K: Return<Succ<V>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Value, V1: Value, R: Value> Return<V> for Push<PredF, K>
+where
+    V: PredResult<Res = V1>,
+    K: Return<V1, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Value, V1: Value, R: Value> Return<V> for Push<IsZeroF, K>
+where
+    V: IsZeroResult<Res = V1>,
+    K: Return<V1, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, F: Abs, T: Term, R: Value> Return<F> for Push<FixF, K>
+where
+    F::Body: Instantiate<Fix<F>, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, Z: Term, S: Term, V: Value, T: Term, R: Value> Return<V> for Push<NatRecF<Z, S>, K>
+where
+    V: NatRecStep<Z, S, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+// No exception was raised, the handler is dropped
+impl<K: Stack, H: Term, V: Value, R: Value> Return<V> for Push<TryF<H>, K>
+where
+    K: Return<V, Res = R>,
+{
+    type Res = R;
+}
+// The captured continuation is the stack below the CallCC frame
+impl<K: Stack, F: Abs, T: Term, R: Value> Return<F> for Push<CallCCF, K>
+where
+    F::Body: Beta<ContV<K>, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, K1: Stack, V: Term, R: Value> Return<ContV<K1>> for Push<ThrowL<V>, K>
+where
+    V: Run<Push<ThrowR<K1>, K>, Res = R>,
+{
+    type Res = R;
+}
+// The current stack is discarded in favor of the captured one
+impl<K: Stack, K1: Stack, V: Value, R: Value> Return<V> for Push<ThrowR<K1>, K>
+where
+    K1: Return<V, Res = R>,
+{
+    type Res = R;
+}
+// Once a field is a value, the remaining ones are evaluated as a record
+// and the value is put back in front of it
+impl<K: Stack, L: Label, Tl: FieldList, V: Plain, R: Value> Return<V> for Push<FieldF<L, Tl>, K>
+where
+    Record<Tl>: Run<Push<ConsFieldF<L, V>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, L: Label, V: Plain, Vs: FieldList + FieldValues, R: Value> Return<Record<Vs>>
+    for Push<ConsFieldF<L, V>, K>
+where
+    K: Return<Record<FieldCons<L, V, Vs>>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, L: Label, Vs: FieldList + FieldValues, V: Value, R: Value> Return<Record<Vs>>
+    for Push<ProjF<L>, K>
+where
+    Vs: GetField<L, Res = V>,
+    K: Return<V, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, L: Label, Tp: Type, V: Plain, R: Value> Return<V> for Push<TagF<L, Tp>, K>
+where
+    K: Return<Tag<L, V, Tp>, Res = R>,
+{
+    type Res = R;
+}
+// The payload is substituted in the branch of its case
+impl<K: Stack, Bs: BranchList, L: Label, V: Plain, Tp: Type, B: Term, T: Term, R: Value>
+    Return<Tag<L, V, Tp>> for Push<MatchF<Bs>, K>
+where
+    Bs: Branch<L, Res = B>,
+    B: Beta<V, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+// The head of a list is evaluated before its tail
+impl<K: Stack, T: Term, H: Plain, R: Value> Return<H> for Push<ConsF<T>, K>
+where
+    T: Run<Push<ConsTailF<H>, K>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, H: Plain, V: Plain, R: Value> Return<V> for Push<ConsTailF<H>, K>
+where
+    K: Return<Cons<H, V>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, N: Term, B: Term, V: Value, T: Term, R: Value> Return<V> for Push<ListCaseF<N, B>, K>
+where
+    V: ListStep<N, B, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, H: Type, ExTp: Type, V: Plain, R: Value> Return<V> for Push<PackF<H, ExTp>, K>
+where
+    K: Return<Pack<H, V, ExTp>, Res = R>,
+{
+    type Res = R;
+}
+// Only the term of the package is substituted, as with Eval
+impl<K: Stack, B: Term, H: Type, V: Plain, ExTp: Type, T: Term, R: Value> Return<Pack<H, V, ExTp>>
+    for Push<UnpackF<B>, K>
+where
+    B: Beta<V, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+// Casts are performed as with Eval, the result of a projection is run
+// rather than returned so that Blame can discard the stack
+impl<K: Stack, V: Plain, R: Value> Return<V> for Push<CastF<Bool, Bool>, K>
+where
+    K: Return<V, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, R: Value> Return<V> for Push<CastF<Nat, Nat>, K>
+where
+    K: Return<V, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, R: Value> Return<V> for Push<CastF<Unit, Unit>, K>
+where
+    K: Return<V, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, R: Value> Return<V> for Push<CastF<Dyn, Dyn>, K>
+where
+    K: Return<V, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, G: Ground, R: Value> Return<V> for Push<CastF<G, Dyn>, K>
+where
+    K: Return<Cast<V, G, Dyn>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, A: Type, B: Type, C: Type, D: Type, R: Value> Return<V>
+    for Push<CastF<Arrow<A, B>, Arrow<C, D>>, K>
+where
+    K: Return<Cast<V, Arrow<A, B>, Arrow<C, D>>, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, V: Plain, G: Ground, To: Ground, V1: Value, R: Value> Return<Cast<V, G, Dyn>>
+    for Push<CastF<Dyn, To>, K>
+where
+    G: Project<V, To, Res = V1>,
+    V1: Run<K, Res = R>,
+{
+    type Res = R;
+}
+
+// E-Raise, frames are popped until a handler is found
+impl<K: Stack, V: Value, R: Value> Return<V> for Push<RaiseF, K>
+where
+    K: Unwind<V, Res = R>,
+{
+    type Res = R;
+}
+trait Unwind<V: Value>: Stack {
+    type Res: Value;
+}
+impl<V: Plain> Unwind<V> for Halt {
+    type Res = Raise<V>;
+}
+impl<K: Stack, H: Term, V: Value, T: Term, R: Value> Unwind<V> for Push<TryF<H>, K>
+where
+    H: Beta<V, Res = T>,
+    T: Run<K, Res = R>,
+{
+    type Res = R;
+}
+impl<K: Stack, F: Frame + NoHandler, V: Value, R: Value> Unwind<V> for Push<F, K>
+where
+    K: Unwind<V, Res = R>,
+{
+    type Res = R;
+}
+
+// Every frame but TryF
+trait NoHandler {}
+impl<T: Term> NoHandler for AppL<T> {}
+impl<F: Value> NoHandler for AppR<F> {}
+impl<T1: Term, T2: Term> NoHandler for IfF<T1, T2> {}
+impl<Body: Term> NoHandler for LetF<Body> {}
+impl NoHandler for SuccF {}
+impl NoHandler for PredF {}
+impl NoHandler for IsZeroF {}
+impl NoHandler for FixF {}
+impl<Z: Term, S: Term> NoHandler for NatRecF<Z, S> {}
+impl NoHandler for RaiseF {}
+impl NoHandler for CallCCF {}
+impl<V: Term> NoHandler for ThrowL<V> {}
+impl<K: Stack> NoHandler for ThrowR<K> {}
+impl<L: Label, Tl: FieldList> NoHandler for FieldF<L, Tl> {}
+impl<L: Label, V: Value> NoHandler for ConsFieldF<L, V> {}
+impl<L: Label> NoHandler for ProjF<L> {}
+impl<L: Label, Tp: Type> NoHandler for TagF<L, Tp> {}
+impl<Bs: BranchList> NoHandler for MatchF<Bs> {}
+impl<T: Term> NoHandler for ConsF<T> {}
+impl<H: Value> NoHandler for ConsTailF<H> {}
+impl<N: Term, B: Term> NoHandler for ListCaseF<N, B> {}
+impl<H: Type, ExTp: Type> NoHandler for PackF<H, ExTp> {}
+impl<B: Term> NoHandler for UnpackF<B> {}
+impl<From: Type, To: Type> NoHandler for CastF<From, To> {}
+
+/// Evaluates T with the abstract machine, starting from the empty stack
+pub fn run<T>() -> PhantomData<<T as Run<Halt>>::Res>
+where
+    T: Term,
+    T: Run<Halt>,
+{
+    PhantomData
+}
+
+pub fn run_to<T, V>()
+where
+    T: Term,
+    V: Value,
+    T: Run<Halt, Res = V>,
+{
+}
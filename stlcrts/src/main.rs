@@ -1,5 +1,15 @@
 use stlcrts::*;
-use stlcrts_macros::stlc;
+use stlcrts_macros::{names, stlc, stlc_defs, stlc_eval, ulc};
+
+// Top-level definitions, which later programs refer to by name
+stlc_defs! {
+    def not (b: Bool) : Bool = if b then false else true;
+    def add (a: Nat) (b: Nat) : Nat = if iszero a then b else succ (add (pred a) b);
+    def double (n: Nat) : Nat = add n n;
+    def even (n: Nat) : Bool = natrec true (fn k => fn acc => not acc) n;
+    def three : Nat = succ (double 1);
+    def echo (n: Nat) : Nat = print n; n;
+}
 
 fn main() {
     type_checks::<
@@ -76,4 +86,503 @@ fn main() {
                     succ (if iszerofn2 0 then 1 else 2)
         },
     >();
+
+    type_checks_in::<TyCons<Nat, EmptyEnv>, Succ<Var<I0>>>();
+
+    let _tp: std::marker::PhantomData<Bool> =
+        type_of_in::<TyCons<Arrow<Nat, Bool>, EmptyEnv>, App<Var<I0>, Zero>>();
+
+    type OpenProg = stlc! {
+        with x: Nat, f: (Nat -> Bool);
+        if f x then succ x else x
+    };
+    type_checks_in::<<OpenProg as OpenTerm>::Env, <OpenProg as OpenTerm>::Body>();
+
+    eval_to_in::<
+        ValCons<stlc! { 2 }, ValCons<stlc! { iszero }, EmptyVals>>,
+        <OpenProg as OpenTerm>::Body,
+        stlc! { 2 },
+    >();
+
+    eval_to::<stlc! { ((fn b => if b then false else true) : (Bool -> Bool)) true }, False>();
+
+    eval_to::<
+        stlc! {
+            let twice = fn f: (Nat -> Nat) => fn n: Nat => f (f n) in
+            twice (fn n => succ n) 1
+        },
+        stlc! { 3 },
+    >();
+
+    type_checks::<
+        stlc! {
+            let rec add (a: Nat) (b: Nat) : Nat =
+                if iszero a then b else succ (add (pred a) b)
+            in (add 2 : (Nat -> Nat))
+        },
+    >();
+
+    eval_to::<
+        stlc! {
+            let rec even (n: Nat) : Bool =
+                if iszero n then true else odd (pred n)
+            and odd (n: Nat) : Bool =
+                if iszero n then false else even (pred n)
+            in
+            if even 4 then odd 3 else false
+        },
+        True,
+    >();
+    // The functions of a group see the variables bound around it, and the
+    // scope sees both
+    eval_to::<
+        stlc_eval! {
+            #![verify]
+            let step = 3 in
+            let rec a (n: Nat) : Nat = if iszero n then step else b (pred n)
+            and b (n: Nat) : Nat = if iszero n then 0 else c (pred n)
+            and c (n: Nat) : Nat = if iszero n then succ step else a (pred n)
+            in
+            let k = 2 in
+            (fn m: Nat => c (a m)) (succ k)
+        },
+        stlc! { 4 },
+    >();
+
+    type TotalAdd = stlc! {
+        fn a: Nat => fn b: Nat => natrec b (fn n => fn acc => succ acc) a
+    };
+    is_total::<TotalAdd>();
+    type_checks::<TotalAdd>();
+    eval_to::<App<App<TotalAdd, stlc! { 2 }>, stlc! { 3 }>, stlc! { 5 }>();
+
+    type SafePred = stlc! {
+        fn n: Nat => if iszero n then (raise 0 : Nat) else pred n
+    };
+    type_checks::<SafePred>();
+    eval_to::<Try<App<SafePred, Zero>, stlc! { 42 }>, stlc! { 42 }>();
+    eval_to::<App<SafePred, Zero>, Raise<Zero>>();
+
+    // Either branch of an if can be the one which raises
+    type RaiseThen = stlc! { fn n: Nat => if iszero n then raise 0 else pred n };
+    type RaiseElse = stlc! { fn n: Nat => if not (iszero n) then pred n else raise 0 };
+    type_checks::<RaiseThen>();
+    type_checks::<RaiseElse>();
+    eval_to::<App<RaiseThen, stlc! { 3 }>, stlc! { 2 }>();
+    eval_to::<App<RaiseElse, Zero>, Raise<Zero>>();
+    eval_to::<
+        stlc_eval! { #![verify] (fn n: Nat => if iszero n then raise 0 else pred n) 3 },
+        stlc! { 2 },
+    >();
+
+    eval_to::<
+        stlc! {
+            let not = fn b: Bool => if b then false else true in
+            let check = fn n: Nat => if not (iszero n) then n else raise 1 in
+            try succ (check 0) with e => succ e
+        },
+        stlc! { 2 },
+    >();
+
+    type Counter = stlc! {
+        let c = ref 0 in
+        let incr = fn u: Unit => c := succ !c in
+        let _a = incr () in
+        let _b = incr () in
+        !c
+    };
+    type_checks::<Counter>();
+    eval_to_with_store::<Counter, stlc! { 2 }, StoreCons<stlc! { 2 }, EmptyStore>>();
+
+    let _res: std::marker::PhantomData<(Loc<I0>, StoreCons<True, EmptyStore>)> =
+        eval_with_store::<stlc! { ref true }>();
+
+    eval_io_to::<
+        stlc! { print 1; print true; 3 },
+        LogCons<stlc! { 1 }, LogCons<True, EmptyLog>>,
+        stlc! { 3 },
+    >();
+    eval_io_to::<
+        stlc! { let p = {x = 1, y = 2} in print p.y; p },
+        LogCons<stlc! { 2 }, EmptyLog>,
+        stlc! { {x = 1, y = 2} },
+    >();
+
+    let (log, res) = eval_io::<
+        stlc! {
+            let c = ref 0 in
+            let rec count (n: Nat) : Unit =
+                if iszero n then () else (c := succ !c; print !c; count (pred n))
+            in
+            count 3;
+            !c
+        },
+    >();
+    assert_eq!(log, [1, 2, 3].map(RuntimeValue::Nat));
+    assert_eq!(res, RuntimeValue::Nat(3));
+
+    // Escaping with call/cc, the continuation discards the pending succ
+    type Escape = stlc! {
+        callcc (fn k: Cont Nat => succ (if iszero 0 then throw k 1 else 2 : Nat))
+    };
+    type_checks::<Escape>();
+    run_to::<Escape, stlc! { 1 }>();
+    run_to::<stlc! { succ (callcc (fn k: Cont Nat => 2)) }, stlc! { 3 }>();
+    run_to::<stlc! { try succ (raise 1 : Nat) with e => e }, stlc! { 1 }>();
+
+    // Records and projections, fields are looked up by label
+    type Point = stlc! { {x = 1, y = true} };
+    type_checks::<Point>();
+    eval_to::<stlc! { {x = 1, y = true}.y }, True>();
+    eval_to::<
+        stlc! {
+            let norm = fn p: {x: Nat, y: Nat} => natrec p.x (fn n => fn acc => succ acc) p.y in
+            norm {x = 2, y = 3}
+        },
+        stlc! { 5 },
+    >();
+    eval_to::<stlc! { try {a = raise 1, b = 2}.b with e => e }, stlc! { 1 }>();
+    eval_to_with_store::<
+        stlc! { let r = ref 1 in {a = !r}.a },
+        stlc! { 1 },
+        StoreCons<stlc! { 1 }, EmptyStore>,
+    >();
+    run_to::<stlc! { {x = 1, y = succ 1}.y }, stlc! { 2 }>();
+
+    // Variants, a match must handle every case
+    type OrZero = stlc! {
+        fn o: <none: Unit, some: Nat> => case o of <none = u> => 0 | <some = n> => n
+    };
+    type_checks::<OrZero>();
+    eval_to::<
+        stlc! {
+            let get = fn o: <none: Unit, some: Nat> => case o of <some = n> => n | <none = u> => 0 in
+            get (<some = 3> as <none: Unit, some: Nat>)
+        },
+        stlc! { 3 },
+    >();
+    eval_to::<App<OrZero, stlc! { <none = ()> as <none: Unit, some: Nat> }>, Zero>();
+    run_to::<App<OrZero, stlc! { <some = succ 2> as <none: Unit, some: Nat> }>, stlc! { 3 }>();
+    eval_to_with_store::<
+        stlc! {
+            let r = ref 1 in
+            case <some = !r> as <none: Unit, some: Nat> of <none = u> => 0 | <some = n> => (r := n; succ !r)
+        },
+        stlc! { 2 },
+        StoreCons<stlc! { 1 }, EmptyStore>,
+    >();
+
+    // Lists of Nats, with literals, cons and case analysis
+    type Sum = stlc! {
+        let rec sum (l: List Nat) : Nat =
+            case l of [] => 0 | h :: t => natrec h (fn n => fn acc => succ acc) (sum t)
+        in
+        sum
+    };
+    type_checks::<Sum>();
+    eval_to::<App<Sum, stlc! { [1, 2, 3] }>, stlc! { 6 }>();
+    eval_to::<App<Sum, stlc! { 4 :: ([] : List Nat) }>, stlc! { 4 }>();
+    eval_to::<stlc! { case [true, false] of [] => [] | h :: t => t }, Cons<False, CNil>>();
+    run_to::<stlc! { case [succ 1, 3] of [] => 0 | h :: t => h }, stlc! { 2 }>();
+    eval_to_with_store::<
+        stlc! { let r = ref 2 in case [!r, 3] of [] => 0 | h :: t => h },
+        stlc! { 2 },
+        StoreCons<stlc! { 2 }, EmptyStore>,
+    >();
+
+    // Structural subtyping, a record with more fields can be passed where fewer are expected
+    type Point3 = stlc! { {x = 1, y = 2, z = true} };
+    type GetX = stlc! { fn p: {x: Nat} => p.x };
+    subtype_checks::<App<GetX, Point3>>();
+    eval_to::<App<GetX, Point3>, stlc! { 1 }>();
+    subtype_checks::<
+        stlc! { (fn f: ({x: Nat, y: Nat} -> Top) => f {y = 2, x = 1}) (fn p: {x: Nat} => p.x) },
+    >();
+    is_subtype::<Arrow<Top, Nat>, Arrow<Bool, Top>>();
+
+    // Existentials, an abstract counter whose representation is hidden
+    type AbsCounter = stlc! {
+        pack Nat, {new = 0, inc = fn n: Nat => succ n, get = fn n: Nat => n}
+        as exists X. {new: X, inc: (X -> X), get: (X -> Nat)}
+    };
+    type_checks::<AbsCounter>();
+    eval_to::<
+        stlc! {
+            let {X, c} = pack Nat, {new = 0, inc = fn n: Nat => succ n, get = fn n: Nat => n}
+                as exists X. {new: X, inc: (X -> X), get: (X -> Nat)} in
+            let twice = fn x: X => c.inc (c.inc x) in
+            c.get (twice c.new)
+        },
+        stlc! { 2 },
+    >();
+    run_to::<stlc! { let {X, c} = $AbsCounter in c.get (c.inc c.new) }, stlc! { 1 }>();
+    eval_to_with_store::<
+        stlc! {
+            let r = ref 1 in
+            let {X, c} = pack Nat, {new = !r, get = fn n: Nat => n} as exists X. {new: X, get: (X -> Nat)} in
+            c.get c.new
+        },
+        stlc! { 1 },
+        StoreCons<stlc! { 1 }, EmptyStore>,
+    >();
+
+    // Gradual typing, parameters without annotation have type Dyn and casts check them at runtime
+    type Twice = stlc! { fn f => fn x => f (f x) };
+    gradual_checks::<Twice>();
+    gradual_eval_to::<
+        App<App<Twice, stlc! { fn n: Nat => succ n }>, Zero>,
+        Cast<stlc! { 2 }, Nat, Dyn>,
+    >();
+    gradual_eval_to::<stlc! { (fn x => succ x) 1 }, stlc! { 2 }>();
+    // Dyn can be hidden in a package like any other type
+    type DynPack = stlc! { pack Nat, {f = fn y: Dyn => y} as exists X. {f: (Dyn -> Dyn)} };
+    type_checks::<DynPack>();
+    eval_to::<
+        stlc_eval! { #![verify] pack Nat, {f = fn y: Dyn => y} as exists X. {f: (Dyn -> Dyn)} },
+        DynPack,
+    >();
+    gradual_eval_to::<stlc! { (fn x => succ x) true }, Blame>();
+    gradual_eval_to::<stlc! { let f = ((fn n: Nat => n) : Dyn) in (f : (Bool -> Bool)) true }, Blame>(
+    );
+    gradual_eval_to::<stlc! { if (true : Dyn) then 1 else (2 : Dyn) }, stlc! { 1 }>();
+    // The casts are inserted once, any evaluator can then run them
+    type TwiceSucc =
+        <App<App<Twice, stlc! { fn n: Nat => succ n }>, Zero> as CastInsert<EmptyEnv>>::Res;
+    run_to::<<stlc! { (fn x => succ x) 1 } as CastInsert<EmptyEnv>>::Res, stlc! { 2 }>();
+    eval_to_with_store::<TwiceSucc, Cast<stlc! { 2 }, Nat, Dyn>, EmptyStore>();
+    // Handlers do not catch blame
+    type Blamed = Try<<stlc! { (fn x => succ x) true } as CastInsert<EmptyEnv>>::Res, Zero>;
+    run_to::<Blamed, Blame>();
+    type Mismatch = stlc! { let f = ((fn n: Nat => n) : Dyn) in (f : (Bool -> Bool)) true };
+    run_to::<<Mismatch as CastInsert<EmptyEnv>>::Res, Blame>();
+    eval_to_with_store::<Blamed, Blame, EmptyStore>();
+    is_consistent::<Arrow<Dyn, Nat>, Arrow<Bool, Dyn>>();
+
+    // Untyped lambda calculus, the Z combinator gives recursion without Fix
+    type Z = ulc! { fn f => (fn x => f (fn v => x x v)) (fn x => f (fn v => x x v)) };
+    type Double = ulc! { fn d => fn n => if iszero n then 0 else succ (succ (d (pred n))) };
+    eval_to::<App<App<Z, Double>, stlc! { 3 }>, stlc! { 6 }>();
+    eval_to::<ulc! { (fn x => x) (fn y => y y) }, ULam<App<Var<I0>, Var<I0>>>>();
+    // Erasing a well-typed term commutes with evaluation
+    erasure_agrees::<stlc! { let not = fn b: Bool => if b then false else true in not (not true) }>(
+    );
+    erasure_agrees::<stlc! { (fn f: (Nat -> Nat) => f (f 1)) (fn n: Nat => succ n) }>();
+    erasure_agrees::<stlc! { try succ (raise 2 : Nat) with e => e }>();
+    erasure_agrees::<stlc! { {x = 1, y = [true]}.y }>();
+    erasure_agrees::<App<OrZero, stlc! { <some = 3> as <none: Unit, some: Nat> }>>();
+    erasure_agrees::<stlc! { <some = 3> as <none: Unit, some: Nat> }>();
+    erasure_agrees::<App<Sum, stlc! { [1, 2] }>>();
+    erasure_agrees::<stlc! { let {X, c} = $AbsCounter in c.get (c.inc c.new) }>();
+    erasure_agrees::<IsEven>();
+    // and with the evaluators of printing and continuations
+    eval_io_to::<<stlc! { print 1; 2 } as Erase>::Res, LogCons<stlc! { 1 }, EmptyLog>, stlc! { 2 }>(
+    );
+    run_to::<
+        <stlc! { succ (callcc (fn k: Cont Nat => succ (throw k 1 : Nat))) } as Erase>::Res,
+        stlc! { 2 },
+    >();
+
+    // As an item, stlc! emits the terms which occur several times once, as hidden aliases
+    stlc! {
+        type IsEven =
+            let rec even (n: Nat) : Bool = if iszero n then true else odd (pred n)
+            and odd (n: Nat) : Bool = if iszero n then false else even (pred n)
+            in even 4
+    }
+    eval_to::<IsEven, True>();
+
+    // stlc_eval! evaluates the program during macro expansion, fact 5 would be
+    // out of reach of the trait-based evaluator
+    type Fact5 = stlc_eval! {
+        let rec add (a: Nat) (b: Nat) : Nat = if iszero a then b else succ (add (pred a) b) in
+        let rec mul (a: Nat) (b: Nat) : Nat = if iszero a then 0 else add b (mul (pred a) b) in
+        let rec fact (a: Nat) : Nat = if iszero a then 1 else mul a (fact (pred a)) in
+        fact 5
+    };
+    type N120 = stlc! { 120 };
+    let _res: std::marker::PhantomData<N120> = std::marker::PhantomData::<Fact5>;
+    // The interpreter keeps its continuation on the heap, so deep recursions are fine
+    type Deep = stlc_eval! {
+        let rec f (n: Nat) : Bool = if iszero n then true else f (pred n) in f 2000
+    };
+    let _res: std::marker::PhantomData<True> = std::marker::PhantomData::<Deep>;
+    type DeepNatRec =
+        stlc_eval! { use prelude; natrec (le 60 60) (fn n: Nat => fn r: Bool => r) 1500 };
+    let _res: std::marker::PhantomData<True> = std::marker::PhantomData::<DeepNatRec>;
+    // With #![verify], rustc checks that the trait-based evaluator agrees
+    type Three = stlc_eval! {
+        #![verify]
+        let twice = fn f: (Nat -> Nat) => fn n: Nat => f (f n) in twice (fn n => succ n) 1
+    };
+    eval_to::<Three, stlc! { 3 }>();
+    type Fields = stlc_eval! {
+        #![verify]
+        let rec even (n: Nat) : Bool = if iszero n then true else odd (pred n)
+        and odd (n: Nat) : Bool = if iszero n then false else even (pred n)
+        in {e = even 4, l = case [1, 2] of [] => 0 | h :: t => h, o = <some = 3> as <none: Unit, some: Nat>}
+    };
+    type_checks::<Fields>();
+    type Unpacked = stlc_eval! {
+        #![verify]
+        let {X, c} = pack Nat, {new = 0, inc = fn n: Nat => succ n, get = fn n: Nat => n}
+            as exists X. {new: X, inc: (X -> X), get: (X -> Nat)} in
+        try c.get (c.inc c.new) with e => e
+    };
+    eval_to::<Unpacked, stlc! { 1 }>();
+    eval_to::<stlc_eval! { #![verify] succ (raise 3 : Nat) }, Raise<stlc! { 3 }>>();
+    eval_to::<
+        stlc_eval! { #![verify] (fn n: Nat => natrec n (fn k => fn acc => succ acc) 2) 3 },
+        stlc! { 5 },
+    >();
+    type Loop = stlc_eval! { #![verify] let rec f (n: Nat) : Nat = f n in f };
+    type_checks::<Loop>();
+    // As an item, the value is checked by an eval_to assertion even if the alias is unused
+    stlc_eval! {
+        #![verify]
+        type Two = let f = fn n: Nat => succ n in f 1
+    }
+    eval_to::<Two, stlc! { 2 }>();
+
+    // #![opt] drops unused lets, inlines those used once and folds literals
+    let _res: std::marker::PhantomData<False> = std::marker::PhantomData::<
+        stlc! { #![opt] let unused = fn n: Nat => n in let x = succ 2 in iszero (pred x) },
+    >;
+    type Inlined = stlc! {
+        #![opt]
+        with y: Nat, w: Bool;
+        let x = y in fn z: Nat => let q = w in if q then succ x else z
+    };
+    type_checks_in::<<Inlined as OpenTerm>::Env, <Inlined as OpenTerm>::Body>();
+    eval_to_in::<
+        ValCons<stlc! { 2 }, ValCons<True, EmptyVals>>,
+        App<<Inlined as OpenTerm>::Body, Zero>,
+        stlc! { 3 },
+    >();
+    // Inlining under an unpack shifts the type variables of the inlined term
+    type InlinedUnder = stlc! {
+        #![opt]
+        let {X, c} = pack Nat, {z = 0, f = fn n: Nat => succ n} as exists X. {z: X, f: (X -> Nat)} in
+        let g = fn x: X => c.f x in
+        let {Y, d} = pack Bool, {z = true} as exists Y. {z: Y} in
+        g c.z
+    };
+    type_checks::<InlinedUnder>();
+    eval_to::<InlinedUnder, stlc! { 1 }>();
+    // A binding which raises is kept
+    eval_to::<stlc! { #![opt] let u = (raise 1 : Nat) in 2 }, Raise<stlc! { 1 }>>();
+    // Programs using what the native type checker does not support are left as they are
+    eval_io_to::<stlc! { #![opt] let u = print 1 in 2 }, LogCons<stlc! { 1 }, EmptyLog>, stlc! { 2 }>(
+    );
+    eval_to::<stlc! { #![opt] let x = succ 1 in double x }, stlc! { 4 }>();
+    eval_to::<
+        stlc_eval! { #![opt] #![verify] let a = 1 in let b = fn n: Nat => succ n in b (b a) },
+        stlc! { 3 },
+    >();
+    // The terms of the prelude are known, so its functions can be inlined and evaluated natively
+    let _res: std::marker::PhantomData<App<prelude::not, True>> =
+        std::marker::PhantomData::<stlc! { #![opt] use prelude; let unused = 1 in not true }>;
+    type PreludeFact5 = stlc_eval! { use prelude; fact 5 };
+    let _res: std::marker::PhantomData<N120> = std::marker::PhantomData::<PreludeFact5>;
+    stlc_eval! {
+        #![opt]
+        #![verify]
+        type Mul = use prelude; fn n: Nat => if le n 3 then mul n 2 else 0
+    }
+    eval_to::<App<Mul, stlc! { 2 }>, stlc! { 4 }>();
+
+    // Named variables, converted to de Bruijn indices by ToDeBruijn, in a block so that
+    // the labels of names! do not shadow the definitions of stlc_defs!
+    {
+        names!(a, b, and, not);
+        type NamedAnd = NLet<
+            not,
+            NLam<b, Bool, If<NVar<b>, False, True>>,
+            NLet<
+                and,
+                NLam<a, Bool, NLam<b, Bool, If<NVar<a>, NVar<b>, False>>>,
+                App<App<NVar<and>, True>, App<NVar<not>, False>>,
+            >,
+        >;
+        named_type_checks::<NamedAnd>();
+        named_eval_to::<NamedAnd, True>();
+        // The innermost binding of a name shadows the others
+        eval_to::<DeBruijn<NLam<a, Nat, NLam<a, Bool, NVar<a>>>>, Lam<Nat, Lam<Bool, Var<I0>>>>();
+        eval_to::<
+            DeBruijn<NLam<a, Nat, NLam<b, Bool, NVar<a>>>>,
+            Lam<Nat, Lam<Bool, Var<ISucc<I0>>>>,
+        >();
+        named_eval_to::<App<NLam<a, Bool, NLam<b, Bool, NVar<a>>>, True>, Lam<Bool, True>>();
+        // Every binder has a named form
+        names!(h, tl, e, none, some, c, ok);
+        named_eval_to::<NTry<Raise<stlc! { 1 }>, e, Succ<NVar<e>>>, stlc! { 2 }>();
+        named_eval_to::<
+            NListCase<stlc! { [1, 2] }, Zero, h, tl, NListCase<NVar<tl>, Zero, h, tl, NVar<h>>>,
+            stlc! { 2 },
+        >();
+        named_eval_to::<
+            Match<
+                stlc! { <some = 3> as <none: Unit, some: Nat> },
+                NBranchCons<none, e, Zero, NBranchCons<some, e, Succ<NVar<e>>, BranchNil>>,
+            >,
+            stlc! { 4 },
+        >();
+        type NamedUnpack = NUnpack<
+            c,
+            stlc! { pack Nat, {new = 0, ok = true} as exists X. {new: X, ok: Bool} },
+            Proj<NVar<c>, ok>,
+        >;
+        named_type_checks::<NamedUnpack>();
+        named_eval_to::<NamedUnpack, True>();
+    }
+
+    // Rust type aliases can be spliced into programs with $Name or #{Type}, they must be closed
+    type Not = stlc! { fn b: Bool => if b then false else true };
+    type Add = stlc! { let rec add (a: Nat) (b: Nat) : Nat = if iszero a then b else succ (add (pred a) b) in add };
+    eval_to::<
+        stlc! { let and = fn a: Bool => fn b: Bool => if a then b else false in and true ($Not false) },
+        True,
+    >();
+    eval_to::<
+        stlc! { (fn n: Nat => fn m: Nat => $Add n (#{App<Add, stlc! { 1 }>} m)) 2 3 },
+        stlc! { 6 },
+    >();
+    stlc! {
+        type Tagged = case <some = $Add 1 2> as <none: Unit, some: Nat> of
+            <none = u> => false | <some = n> => #{Not} (iszero n)
+    }
+    eval_to::<Tagged, True>();
+
+    // Definitions of stlc_defs! are free variables bound to their definition
+    eval_to::<stlc! { double 3 }, stlc! { 6 }>();
+    eval_to::<stlc! { let f = fn n: Nat => not (even n) in f 3 }, True>();
+    type_checks_as::<add, Arrow<Nat, Arrow<Nat, Nat>>>();
+    // The body of a definition can be a sequence
+    eval_io_to::<
+        stlc! { echo (echo 1) },
+        LogCons<stlc! { 1 }, LogCons<stlc! { 1 }, EmptyLog>>,
+        stlc! { 1 },
+    >();
+
+    // The prelude, `use prelude;` binds the functions of stlcrts::prelude the program uses
+    eval_to::<stlc! { use prelude; if le 2 3 then fact 3 else 0 }, stlc! { 6 }>();
+    eval_to::<stlc! { use prelude; and (eq (add 1 1) 2) (not (or false false)) }, True>();
+    // `and` is only a keyword where it starts the next function of a let rec group
+    eval_to::<stlc! { use prelude; (fn f: (Bool -> (Bool -> Bool)) => f true true) and }, True>();
+    eval_to::<
+        stlc! {
+            use prelude;
+            let rec even (n: Nat) : Bool = if iszero n then true else and true (odd (pred n))
+            and odd (n: Nat) : Bool = if iszero n then false else even (pred n)
+            in even 2
+        },
+        True,
+    >();
+    // The names bound by the program itself shadow those of the prelude
+    eval_to::<stlc! { use prelude; let not = fn b: Bool => b in not true }, True>();
+    type Square = stlc! {
+        use prelude;
+        with x: Nat;
+        mul x x
+    };
+    eval_to_in::<ValCons<stlc! { 3 }, EmptyVals>, <Square as OpenTerm>::Body, stlc! { 9 }>();
 }
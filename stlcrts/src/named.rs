@@ -0,0 +1,364 @@
+use crate::{
+    evaluator::{Eval, Value},
+    term::*,
+    typechecker::WellTyped,
+};
+use std::marker::PhantomData;
+
+// The names bound around a term, the innermost one first
+pub trait NameEnv {}
+pub struct NameNil;
+pub struct NameCons<X: Label, Tl: NameEnv>(PhantomData<(X, Tl)>);
+impl NameEnv for NameNil {}
+impl<X: Label, Tl: NameEnv> NameEnv for NameCons<X, Tl> {}
+
+// The index of the innermost binding of X, there is no impl when X is unbound
+pub trait NameLookup<X: Label>: NameEnv {
+    type Res: Index;
+}
+trait NameLookupIf<X: Label, Tl: NameEnv> {
+    type Res: Index;
+}
+impl<X: Label, Tl: NameEnv> NameLookupIf<X, Tl> for True {
+    type Res = I0;
+}
+impl<X: Label, Tl: NameEnv, I: Index> NameLookupIf<X, Tl> for False
+where
+    Tl: NameLookup<X, Res = I>,
+{
+    type Res = ISucc<I>;
+}
+impl<X: Label, Y: Label, Tl: NameEnv, B: Term, I: Index> NameLookup<X> for NameCons<Y, Tl>
+where
+    Y: LabelEq<X, Res = B>,
+    B: NameLookupIf<X, Tl, Res = I>,
+{
+    type Res = I;
+}
+
+// Conversion of a term with named variables to de Bruijn indices, under the
+// names bound in Env. Binders must be the named ones, NLam, NCLam, NLet,
+// NTry, NBranchCons, NListCase and NUnpack, the other terms are converted
+// component-wise.
+pub trait ToDeBruijn<Env: NameEnv = NameNil> {
+    type Res: Term;
+}
+
+impl<Env: NameEnv, X: Label, I: Index> ToDeBruijn<Env> for NVar<X>
+where
+    Env: NameLookup<X, Res = I>,
+{
+    type Res = Var<I>;
+}
+
+impl<Env: NameEnv, X: Label, Tp: Type, T: Term, R: Term> ToDeBruijn<Env> for NLam<X, Tp, T>
+where
+    T: ToDeBruijn<NameCons<X, Env>, Res = R>,
+{
+    type Res = Lam<Tp, R>;
+}
+
+impl<Env: NameEnv, X: Label, T: Term, Body: Term, R1: Term, R2: Term> ToDeBruijn<Env>
+    for NLet<X, T, Body>
+where
+    T: ToDeBruijn<Env, Res = R1>,
+    Body: ToDeBruijn<NameCons<X, Env>, Res = R2>,
+{
+    type Res = Let<R1, R2>;
+}
+
+impl<Env: NameEnv, X: Label, T: Term, R: Term> ToDeBruijn<Env> for NCLam<X, T>
+where
+    T: ToDeBruijn<NameCons<X, Env>, Res = R>,
+{
+    type Res = CLam<R>;
+}
+
+impl<Env: NameEnv> ToDeBruijn<Env> for True {
+    type Res = True;
+}
+impl<Env: NameEnv> ToDeBruijn<Env> for False {
+    type Res = False;
+}
+impl<Env: NameEnv> ToDeBruijn<Env> for Zero {
+    type Res = Zero;
+}
+impl<Env: NameEnv> ToDeBruijn<Env> for Triv {
+    type Res = Triv;
+}
+
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Succ<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Succ<R>;
+}
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Pred<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Pred<R>;
+}
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for IsZero<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = IsZero<R>;
+}
+
+impl<Env: NameEnv, T1: Term, T2: Term, R1: Term, R2: Term> ToDeBruijn<Env> for App<T1, T2>
+where
+    T1: ToDeBruijn<Env, Res = R1>,
+    T2: ToDeBruijn<Env, Res = R2>,
+{
+    type Res = App<R1, R2>;
+}
+
+impl<Env: NameEnv, C: Term, T1: Term, T2: Term, Cprime: Term, R1: Term, R2: Term> ToDeBruijn<Env>
+    for If<C, T1, T2>
+where
+    C: ToDeBruijn<Env, Res = Cprime>,
+    T1: ToDeBruijn<Env, Res = R1>,
+    T2: ToDeBruijn<Env, Res = R2>,
+{
+    type Res = If<Cprime, R1, R2>;
+}
+
+impl<Env: NameEnv, T: Term, Tp: Type, R: Term> ToDeBruijn<Env> for Ann<T, Tp>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Ann<R, Tp>;
+}
+
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Fix<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Fix<R>;
+}
+
+impl<Env: NameEnv, Z: Term, S: Term, N: Term, Zprime: Term, Sprime: Term, Nprime: Term>
+    ToDeBruijn<Env> for NatRec<Z, S, N>
+where
+    Z: ToDeBruijn<Env, Res = Zprime>,
+    S: ToDeBruijn<Env, Res = Sprime>,
+    N: ToDeBruijn<Env, Res = Nprime>,
+{
+    type Res = NatRec<Zprime, Sprime, Nprime>;
+}
+
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Raise<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Raise<R>;
+}
+impl<Env: NameEnv, T: Term, X: Label, H: Term, R: Term, Hprime: Term> ToDeBruijn<Env>
+    for NTry<T, X, H>
+where
+    T: ToDeBruijn<Env, Res = R>,
+    H: ToDeBruijn<NameCons<X, Env>, Res = Hprime>,
+{
+    type Res = Try<R, Hprime>;
+}
+
+impl<Env: NameEnv, Fs: FieldList, R: FieldList> ToDeBruijn<Env> for Record<Fs>
+where
+    Fs: FieldsToDeBruijn<Env, Res = R>,
+{
+    type Res = Record<R>;
+}
+trait FieldsToDeBruijn<Env: NameEnv>: FieldList {
+    type Res: FieldList;
+}
+impl<Env: NameEnv> FieldsToDeBruijn<Env> for FieldNil {
+    type Res = FieldNil;
+}
+impl<Env: NameEnv, L: Label, T: Term, Tl: FieldList, R: Term, Rs: FieldList> FieldsToDeBruijn<Env>
+    for FieldCons<L, T, Tl>
+where
+    T: ToDeBruijn<Env, Res = R>,
+    Tl: FieldsToDeBruijn<Env, Res = Rs>,
+{
+    type Res = FieldCons<L, R, Rs>;
+}
+impl<Env: NameEnv, T: Term, L: Label, R: Term> ToDeBruijn<Env> for Proj<T, L>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Proj<R, L>;
+}
+
+impl<Env: NameEnv, L: Label, T: Term, Tp: Type, R: Term> ToDeBruijn<Env> for Tag<L, T, Tp>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Tag<L, R, Tp>;
+}
+impl<Env: NameEnv, T: Term, Bs: BranchList, R: Term, Rs: BranchList> ToDeBruijn<Env>
+    for Match<T, Bs>
+where
+    T: ToDeBruijn<Env, Res = R>,
+    Bs: BranchesToDeBruijn<Env, Res = Rs>,
+{
+    type Res = Match<R, Rs>;
+}
+trait BranchesToDeBruijn<Env: NameEnv>: BranchList {
+    type Res: BranchList;
+}
+impl<Env: NameEnv> BranchesToDeBruijn<Env> for BranchNil {
+    type Res = BranchNil;
+}
+impl<Env: NameEnv, L: Label, X: Label, T: Term, Tl: BranchList, R: Term, Rs: BranchList>
+    BranchesToDeBruijn<Env> for NBranchCons<L, X, T, Tl>
+where
+    T: ToDeBruijn<NameCons<X, Env>, Res = R>,
+    Tl: BranchesToDeBruijn<Env, Res = Rs>,
+{
+    type Res = BranchCons<L, R, Rs>;
+}
+
+impl<Env: NameEnv, Tp: Type> ToDeBruijn<Env> for Nil<Tp> {
+    type Res = Nil<Tp>;
+}
+impl<Env: NameEnv> ToDeBruijn<Env> for CNil {
+    type Res = CNil;
+}
+impl<Env: NameEnv, H: Term, T: Term, Hprime: Term, R: Term> ToDeBruijn<Env> for Cons<H, T>
+where
+    H: ToDeBruijn<Env, Res = Hprime>,
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Cons<Hprime, R>;
+}
+// The tail is bound last, it is index 0 in the cons branch
+impl<
+    Env: NameEnv,
+    L: Term,
+    N: Term,
+    H: Label,
+    Tl: Label,
+    B: Term,
+    Lprime: Term,
+    Nprime: Term,
+    Bprime: Term,
+> ToDeBruijn<Env> for NListCase<L, N, H, Tl, B>
+where
+    L: ToDeBruijn<Env, Res = Lprime>,
+    N: ToDeBruijn<Env, Res = Nprime>,
+    B: ToDeBruijn<NameCons<Tl, NameCons<H, Env>>, Res = Bprime>,
+{
+    type Res = ListCase<Lprime, Nprime, Bprime>;
+}
+
+impl<Env: NameEnv, H: Type, T: Term, ExTp: Type, R: Term> ToDeBruijn<Env> for Pack<H, T, ExTp>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Pack<H, R, ExTp>;
+}
+impl<Env: NameEnv, X: Label, T: Term, B: Term, R: Term, Bprime: Term> ToDeBruijn<Env>
+    for NUnpack<X, T, B>
+where
+    T: ToDeBruijn<Env, Res = R>,
+    B: ToDeBruijn<NameCons<X, Env>, Res = Bprime>,
+{
+    type Res = Unpack<R, Bprime>;
+}
+
+impl<Env: NameEnv, Fs: FnList, K: Index, R: FnList> ToDeBruijn<Env> for FixN<Fs, K>
+where
+    Fs: FnsToDeBruijn<Env, Res = R>,
+{
+    type Res = FixN<R, K>;
+}
+trait FnsToDeBruijn<Env: NameEnv>: FnList {
+    type Res: FnList;
+}
+impl<Env: NameEnv> FnsToDeBruijn<Env> for FnNil {
+    type Res = FnNil;
+}
+impl<Env: NameEnv, Tp: Type, T: Term, Tl: FnList, R: Term, Rs: FnList> FnsToDeBruijn<Env>
+    for FnCons<Tp, T, Tl>
+where
+    T: ToDeBruijn<Env, Res = R>,
+    Tl: FnsToDeBruijn<Env, Res = Rs>,
+{
+    type Res = FnCons<Tp, R, Rs>;
+}
+
+impl<Env: NameEnv, I: Index> ToDeBruijn<Env> for Loc<I> {
+    type Res = Loc<I>;
+}
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Ref<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Ref<R>;
+}
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Deref<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Deref<R>;
+}
+impl<Env: NameEnv, L: Term, T: Term, Lprime: Term, R: Term> ToDeBruijn<Env> for Assign<L, T>
+where
+    L: ToDeBruijn<Env, Res = Lprime>,
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Assign<Lprime, R>;
+}
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for Print<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Print<R>;
+}
+
+impl<Env: NameEnv, T: Term, R: Term> ToDeBruijn<Env> for CallCC<T>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = CallCC<R>;
+}
+impl<Env: NameEnv, K: Term, V: Term, Kprime: Term, R: Term> ToDeBruijn<Env> for Throw<K, V>
+where
+    K: ToDeBruijn<Env, Res = Kprime>,
+    V: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Throw<Kprime, R>;
+}
+
+impl<Env: NameEnv, T: Term, From: Type, To: Type, R: Term> ToDeBruijn<Env> for Cast<T, From, To>
+where
+    T: ToDeBruijn<Env, Res = R>,
+{
+    type Res = Cast<R, From, To>;
+}
+impl<Env: NameEnv> ToDeBruijn<Env> for Blame {
+    type Res = Blame;
+}
+
+/// The closed term T with its names replaced by de Bruijn indices
+pub type DeBruijn<T> = <T as ToDeBruijn>::Res;
+
+/// Util function to check that a closed term with named variables is well typed
+pub fn named_type_checks<T>()
+where
+    T: Term,
+    T: ToDeBruijn,
+    <T as ToDeBruijn>::Res: WellTyped<EmptyEnv>,
+{
+}
+
+/// Util function to check that a closed term with named variables evaluates to V
+pub fn named_eval_to<T, V>()
+where
+    V: Value,
+    T: Term,
+    T: ToDeBruijn,
+    <T as ToDeBruijn>::Res: Eval<Res = V>,
+{
+}
@@ -0,0 +1,22 @@
+//! Common functions, defined once for every program. A program can refer
+//! to them as `prelude::add`, or by their name after a `use prelude;` header:
+//! `stlc! { use prelude; fact 3 }`.
+
+use crate::*;
+use stlcrts_macros::{prelude_defs, stlc};
+
+// not, and, or, add, mul, eq, le and fact, defined in stlcrts_macros/src/prelude.rs
+// so that stlc_eval! and #![opt] know their terms
+prelude_defs!();
+
+// Checked when the crate is built, like the types of the definitions
+const _: fn() = eval_to::<App<not, True>, False>;
+const _: fn() = eval_to::<App<App<and, True>, False>, False>;
+const _: fn() = eval_to::<App<App<or, False>, True>, True>;
+const _: fn() = eval_to::<App<App<add, stlc! { 2 }>, stlc! { 3 }>, stlc! { 5 }>;
+const _: fn() = eval_to::<App<App<mul, stlc! { 2 }>, stlc! { 3 }>, stlc! { 6 }>;
+const _: fn() = eval_to::<App<App<eq, stlc! { 2 }>, stlc! { 2 }>, True>;
+const _: fn() = eval_to::<App<App<eq, stlc! { 2 }>, stlc! { 3 }>, False>;
+const _: fn() = eval_to::<App<App<le, stlc! { 2 }>, stlc! { 3 }>, True>;
+const _: fn() = eval_to::<App<App<le, stlc! { 3 }>, stlc! { 2 }>, False>;
+const _: fn() = eval_to::<App<fact, stlc! { 3 }>, stlc! { 6 }>;
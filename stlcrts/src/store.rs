@@ -0,0 +1,660 @@
+use crate::{
+    evaluator::{
+        Abs, AppK, BetaK, CastK, ConsFieldK, ConsK, ConsTailK, FieldK, FieldValues, FixK, GetField,
+        Ground, IfK, IsZeroK, IsZeroResult, ListCaseK, ListStep, MatchK, NatRecK, NatRecStep,
+        NthBody, PackK, Plain, PredK, PredResult, ProjK, Project, RaiseK, Select, SuccK, TagK,
+        Unfold, UnpackK, Value, instantiate::Instantiate,
+    },
+    term::*,
+};
+use std::marker::PhantomData;
+
+// The store, a list of values where the first allocated location comes first
+pub trait Store {}
+pub struct EmptyStore;
+impl Store for EmptyStore {}
+pub struct StoreCons<V: Value, Tl: Store>(PhantomData<(V, Tl)>);
+impl<V: Value, Tl: Store> Store for StoreCons<V, Tl> {}
+
+// Allocates V at the end of the store, Loc is its location
+pub trait Alloc<V: Value>: Store {
+    type Loc: Index;
+    type Res: Store;
+}
+impl<V: Value> Alloc<V> for EmptyStore {
+    type Loc = I0;
+    type Res = StoreCons<V, EmptyStore>;
+}
+impl<V: Value, W: Value, Tl: Store, L: Index, R: Store> Alloc<V> for StoreCons<W, Tl>
+where
+    Tl: Alloc<V, Loc = L, Res = R>,
+{
+    type Loc = ISucc<L>;
+    type Res = StoreCons<W, R>;
+}
+
+// Reads the value at location I
+pub trait Lookup<I: Index>: Store {
+    type Res: Value;
+}
+impl<V: Value, Tl: Store> Lookup<I0> for StoreCons<V, Tl> {
+    type Res = V;
+}
+impl<I: Index, V: Value, Tl: Store, R: Value> Lookup<ISucc<I>> for StoreCons<V, Tl>
+where
+    Tl: Lookup<I, Res = R>,
+{
+    type Res = R;
+}
+
+// Replaces the value at location I by V
+pub trait Update<I: Index, V: Value>: Store {
+    type Res: Store;
+}
+impl<V: Value, W: Value, Tl: Store> Update<I0, V> for StoreCons<W, Tl> {
+    type Res = StoreCons<V, Tl>;
+}
+impl<I: Index, V: Value, W: Value, Tl: Store, R: Store> Update<ISucc<I>, V> for StoreCons<W, Tl>
+where
+    Tl: Update<I, V, Res = R>,
+{
+    type Res = StoreCons<W, R>;
+}
+
+// Store passing evaluation, evaluating a term under store S results in
+// the value Res and the final store Out. The rules are the same as for
+// Eval, with the store threaded from left to right.
+pub trait EvalSt<S: Store> {
+    type Res: Value;
+    type Out: Store;
+}
+
+// Same as Kont and Bind for Eval, but the continuation also receives the
+// store and returns the final one.
+pub(crate) trait StKont<V: Value, S: Store> {
+    type Res: Value;
+    type Out: Store;
+}
+pub(crate) trait StBind<K, S: Store> {
+    type Res: Value;
+    type Out: Store;
+}
+impl<V: Plain, K: StKont<V, S>, S: Store> StBind<K, S> for V {
+    type Res = <K as StKont<V, S>>::Res;
+    type Out = <K as StKont<V, S>>::Out;
+}
+impl<V: Plain, K, S: Store> StBind<K, S> for Raise<V> {
+    type Res = Raise<V>;
+    type Out = S;
+}
+impl<K, S: Store> StBind<K, S> for Blame {
+    type Res = Blame;
+    type Out = S;
+}
+
+// Values evaluate to themselves and leave the store untouched
+impl<S: Store> EvalSt<S> for True {
+    type Res = True;
+    type Out = S;
+}
+impl<S: Store> EvalSt<S> for False {
+    type Res = False;
+    type Out = S;
+}
+impl<S: Store> EvalSt<S> for Zero {
+    type Res = Zero;
+    type Out = S;
+}
+impl<S: Store> EvalSt<S> for Triv {
+    type Res = Triv;
+    type Out = S;
+}
+impl<S: Store, I: Index> EvalSt<S> for Loc<I> {
+    type Res = Loc<I>;
+    type Out = S;
+}
+impl<S: Store, Tp: Type, T: Term> EvalSt<S> for Lam<Tp, T> {
+    type Res = Lam<Tp, T>;
+    type Out = S;
+}
+impl<S: Store, T: Term> EvalSt<S> for CLam<T> {
+    type Res = CLam<T>;
+    type Out = S;
+}
+
+// E-Ann
+impl<S: Store, T: Term, Tp: Type, V: Value, S1: Store> EvalSt<S> for Ann<T, Tp>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+{
+    type Res = V;
+    type Out = S1;
+}
+
+impl<S: Store, T: Term, V: Value, Tprime: Term, R: Value, S1: Store> StKont<V, S> for BetaK<T>
+where
+    T: Instantiate<V, Res = Tprime>,
+    Tprime: EvalSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+
+// E-App
+impl<S: Store, T2: Term, F: Abs, V2: Value, S2: Store, R: Value, S3: Store> StKont<F, S>
+    for AppK<T2>
+where
+    T2: EvalSt<S, Res = V2, Out = S2>,
+    V2: StBind<BetaK<F::Body>, S2, Res = R, Out = S3>,
+{
+    type Res = R;
+    type Out = S3;
+}
+// E-AppCast
+impl<S: Store, T2: Term, F: Plain, A: Type, B: Type, C: Type, D: Type, R: Value, S1: Store>
+    StKont<Cast<F, Arrow<A, B>, Arrow<C, D>>, S> for AppK<T2>
+where
+    Cast<Cast<App<F, Cast<Cast<T2, C, Dyn>, Dyn, A>>, B, Dyn>, Dyn, D>:
+        EvalSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<S: Store, T1: Term, T2: Term, V1: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for App<T1, T2>
+where
+    T1: EvalSt<S, Res = V1, Out = S1>,
+    V1: StBind<AppK<T2>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Let
+impl<S: Store, T1: Term, T2: Term, V1: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Let<T1, T2>
+where
+    T1: EvalSt<S, Res = V1, Out = S1>,
+    V1: StBind<BetaK<T2>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Fix
+impl<S: Store, F: Abs, R: Value> StKont<F, S> for FixK
+where
+    F::Body: Instantiate<Fix<F>, Res = R>,
+{
+    type Res = R;
+    type Out = S;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Fix<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<FixK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-FixN
+impl<S: Store, Fs: FnList, K: Index, T: Term, T1: Term, R: Value, S1: Store> EvalSt<S>
+    for FixN<Fs, K>
+where
+    Fs: NthBody<K, Res = T>,
+    Fs: Unfold<T, Fs, I0, Res = T1>,
+    T1: EvalSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+
+// E-If
+impl<S: Store, T1: Term, T2: Term, V: Value, T3: Term, R: Value, S1: Store> StKont<V, S>
+    for IfK<T1, T2>
+where
+    V: Select<T1, T2, Res = T3>,
+    T3: EvalSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<S: Store, Cond: Term, T1: Term, T2: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for If<Cond, T1, T2>
+where
+    Cond: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<IfK<T1, T2>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Succ
+impl<S: Store, V: Plain> StKont<V, S> for SuccK {
+    type Res = Succ<V>;
+    type Out = S;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Succ<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<SuccK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-IsZero
+impl<S: Store, V: Value, R: Value> StKont<V, S> for IsZeroK
+where
+    V: IsZeroResult<Res = R>,
+{
+    type Res = R;
+    type Out = S;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for IsZero<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<IsZeroK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Pred
+impl<S: Store, V: Value, R: Value> StKont<V, S> for PredK
+where
+    V: PredResult<Res = R>,
+{
+    type Res = R;
+    type Out = S;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Pred<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<PredK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-NatRec
+impl<S: Store, Z: Term, St: Term, V: Value, T: Term, R: Value, S1: Store> StKont<V, S>
+    for NatRecK<Z, St>
+where
+    V: NatRecStep<Z, St, Res = T>,
+    T: EvalSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<S: Store, Z: Term, St: Term, N: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for NatRec<Z, St, N>
+where
+    N: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<NatRecK<Z, St>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Raise
+impl<S: Store, V: Plain> StKont<V, S> for RaiseK {
+    type Res = Raise<V>;
+    type Out = S;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Raise<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<RaiseK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Try, changes made to the store before the exception are kept
+trait StCatch<H: Term, S: Store> {
+    type Res: Value;
+    type Out: Store;
+}
+impl<H: Term, S: Store, V: Plain> StCatch<H, S> for V {
+    type Res = V;
+    type Out = S;
+}
+impl<H: Term, S: Store, V: Value, R: Value, S1: Store> StCatch<H, S> for Raise<V>
+where
+    BetaK<H>: StKont<V, S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<H: Term, S: Store> StCatch<H, S> for Blame {
+    type Res = Blame;
+    type Out = S;
+}
+impl<S: Store, T: Term, H: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Try<T, H>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StCatch<H, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Record, the fields are evaluated from left to right, each one with the
+// store left by the previous one
+trait EvalFieldsSt<S: Store>: FieldList {
+    type Res: Value;
+    type Out: Store;
+}
+impl<S: Store> EvalFieldsSt<S> for FieldNil {
+    type Res = Record<FieldNil>;
+    type Out = S;
+}
+impl<S: Store, L: Label, T: Term, Tl: FieldList, V: Value, S1: Store, R: Value, S2: Store>
+    EvalFieldsSt<S> for FieldCons<L, T, Tl>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<FieldK<L, Tl>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+impl<S: Store, L: Label, Tl: FieldList, V: Plain, Vs: Value, S1: Store, R: Value, S2: Store>
+    StKont<V, S> for FieldK<L, Tl>
+where
+    Tl: EvalFieldsSt<S, Res = Vs, Out = S1>,
+    Vs: StBind<ConsFieldK<L, V>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+impl<S: Store, L: Label, V: Plain, Vs: FieldList + FieldValues> StKont<Record<Vs>, S>
+    for ConsFieldK<L, V>
+{
+    type Res = Record<FieldCons<L, V, Vs>>;
+    type Out = S;
+}
+impl<S: Store, Fs: FieldList, R: Value, S1: Store> EvalSt<S> for Record<Fs>
+where
+    Fs: EvalFieldsSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+
+// E-Proj
+impl<S: Store, L: Label, Vs: FieldList + FieldValues, R: Value> StKont<Record<Vs>, S> for ProjK<L>
+where
+    Vs: GetField<L, Res = R>,
+{
+    type Res = R;
+    type Out = S;
+}
+impl<S: Store, T: Term, L: Label, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Proj<T, L>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<ProjK<L>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Tag
+impl<S: Store, L: Label, Tp: Type, V: Plain> StKont<V, S> for TagK<L, Tp> {
+    type Res = Tag<L, V, Tp>;
+    type Out = S;
+}
+impl<S: Store, L: Label, T: Term, Tp: Type, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Tag<L, T, Tp>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<TagK<L, Tp>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Match
+impl<S: Store, Bs: BranchList, L: Label, V: Plain, Tp: Type, B: Term, R: Value, S1: Store>
+    StKont<Tag<L, V, Tp>, S> for MatchK<Bs>
+where
+    Bs: Branch<L, Res = B>,
+    BetaK<B>: StKont<V, S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<S: Store, T: Term, Bs: BranchList, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Match<T, Bs>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<MatchK<Bs>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Nil
+impl<S: Store, Tp: Type> EvalSt<S> for Nil<Tp> {
+    type Res = Nil<Tp>;
+    type Out = S;
+}
+impl<S: Store> EvalSt<S> for CNil {
+    type Res = CNil;
+    type Out = S;
+}
+
+// E-Cons, the head is evaluated before the tail
+impl<S: Store, T: Term, H: Plain, V: Value, S1: Store, R: Value, S2: Store> StKont<H, S>
+    for ConsK<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<ConsTailK<H>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+impl<S: Store, H: Plain, V: Plain> StKont<V, S> for ConsTailK<H> {
+    type Res = Cons<H, V>;
+    type Out = S;
+}
+impl<S: Store, H: Term, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Cons<H, T>
+where
+    H: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<ConsK<T>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-ListCase
+impl<S: Store, N: Term, B: Term, V: Value, T: Term, R: Value, S1: Store> StKont<V, S>
+    for ListCaseK<N, B>
+where
+    V: ListStep<N, B, Res = T>,
+    T: EvalSt<S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<S: Store, L: Term, N: Term, B: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for ListCase<L, N, B>
+where
+    L: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<ListCaseK<N, B>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Pack
+impl<S: Store, H: Type, ExTp: Type, V: Plain> StKont<V, S> for PackK<H, ExTp> {
+    type Res = Pack<H, V, ExTp>;
+    type Out = S;
+}
+impl<S: Store, H: Type, T: Term, ExTp: Type, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Pack<H, T, ExTp>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<PackK<H, ExTp>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-UnpackPack
+impl<S: Store, B: Term, H: Type, V: Plain, ExTp: Type, R: Value, S1: Store>
+    StKont<Pack<H, V, ExTp>, S> for UnpackK<B>
+where
+    BetaK<B>: StKont<V, S, Res = R, Out = S1>,
+{
+    type Res = R;
+    type Out = S1;
+}
+impl<S: Store, T: Term, B: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Unpack<T, B>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<UnpackK<B>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Cast, see CastK for Eval
+impl<S: Store, V: Plain> StKont<V, S> for CastK<Bool, Bool> {
+    type Res = V;
+    type Out = S;
+}
+impl<S: Store, V: Plain> StKont<V, S> for CastK<Nat, Nat> {
+    type Res = V;
+    type Out = S;
+}
+impl<S: Store, V: Plain> StKont<V, S> for CastK<Unit, Unit> {
+    type Res = V;
+    type Out = S;
+}
+impl<S: Store, V: Plain> StKont<V, S> for CastK<Dyn, Dyn> {
+    type Res = V;
+    type Out = S;
+}
+impl<S: Store, V: Plain, G: Ground> StKont<V, S> for CastK<G, Dyn> {
+    type Res = Cast<V, G, Dyn>;
+    type Out = S;
+}
+impl<S: Store, V: Plain, A: Type, B: Type, C: Type, D: Type> StKont<V, S>
+    for CastK<Arrow<A, B>, Arrow<C, D>>
+{
+    type Res = Cast<V, Arrow<A, B>, Arrow<C, D>>;
+    type Out = S;
+}
+impl<S: Store, V: Plain, G: Ground, To: Ground, R: Value> StKont<Cast<V, G, Dyn>, S>
+    for CastK<Dyn, To>
+where
+    G: Project<V, To, Res = R>,
+{
+    type Res = R;
+    type Out = S;
+}
+impl<S: Store, T: Term, From: Type, To: Type, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Cast<T, From, To>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<CastK<From, To>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Blame
+impl<S: Store> EvalSt<S> for Blame {
+    type Res = Blame;
+    type Out = S;
+}
+
+// E-Ref
+struct RefK;
+impl<S: Store, V: Value, L: Index, S1: Store> StKont<V, S> for RefK
+where
+    S: Alloc<V, Loc = L, Res = S1>,
+{
+    type Res = Loc<L>;
+    type Out = S1;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Ref<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<RefK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Deref
+struct DerefK;
+impl<S: Store, I: Index, V: Value> StKont<Loc<I>, S> for DerefK
+where
+    S: Lookup<I, Res = V>,
+{
+    type Res = V;
+    type Out = S;
+}
+impl<S: Store, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S> for Deref<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<DerefK, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+// E-Assign, the location is evaluated first, then the new value
+struct AssignK<T: Term>(PhantomData<T>);
+struct WriteK<I: Index>(PhantomData<I>);
+impl<S: Store, I: Index, T: Term, V: Value, S1: Store, R: Value, S2: Store> StKont<Loc<I>, S>
+    for AssignK<T>
+where
+    T: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<WriteK<I>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+impl<S: Store, I: Index, V: Value, S1: Store> StKont<V, S> for WriteK<I>
+where
+    S: Update<I, V, Res = S1>,
+{
+    type Res = Triv;
+    type Out = S1;
+}
+impl<S: Store, L: Term, T: Term, V: Value, S1: Store, R: Value, S2: Store> EvalSt<S>
+    for Assign<L, T>
+where
+    L: EvalSt<S, Res = V, Out = S1>,
+    V: StBind<AssignK<T>, S1, Res = R, Out = S2>,
+{
+    type Res = R;
+    type Out = S2;
+}
+
+/// Evaluates T starting from an empty store, returns the result and the final store
+pub fn eval_with_store<T>() -> PhantomData<(
+    <T as EvalSt<EmptyStore>>::Res,
+    <T as EvalSt<EmptyStore>>::Out,
+)>
+where
+    T: Term,
+    T: EvalSt<EmptyStore>,
+{
+    PhantomData
+}
+
+pub fn eval_to_with_store<T, V, S>()
+where
+    T: Term,
+    V: Value,
+    S: Store,
+    T: EvalSt<EmptyStore, Res = V, Out = S>,
+{
+}
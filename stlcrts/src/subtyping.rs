@@ -0,0 +1,235 @@
+use crate::{
+    term::*,
+    typechecker::{FieldType, WellTyped},
+};
+use std::marker::PhantomData;
+
+// Self: Subtype<U> if Self is a subtype of U. The rules are the algorithmic
+// ones, there is one rule per type constructor. Reflexivity and
+// transitivity are not rules of their own, as their impls would overlap
+// with every other one, but both are admissible: they can be proven by
+// induction on the types for the rules below.
+pub trait Subtype<U: Type>: Type {}
+
+// S-Top
+impl<T: Type> Subtype<Top> for T {}
+
+// S-Refl, for the base types
+impl Subtype<Bool> for Bool {}
+impl Subtype<Nat> for Nat {}
+impl Subtype<Unit> for Unit {}
+
+// S-Arrow, contravariant in the argument and covariant in the result
+impl<S1: Type, S2: Type, T1: Type, T2: Type> Subtype<Arrow<T1, T2>> for Arrow<S1, S2>
+where
+    T1: Subtype<S1>,
+    S2: Subtype<T2>,
+{
+}
+
+// References can be both read and written, so they are invariant
+impl<Tp: Type> Subtype<RefTy<Tp>> for RefTy<Tp> {}
+
+// A continuation of type Cont<T> accepts any subtype of T
+impl<S: Type, T: Type> Subtype<Cont<T>> for Cont<S> where T: Subtype<S> {}
+
+impl<S: Type, T: Type> Subtype<List<T>> for List<S> where S: Subtype<T> {}
+
+// Type variables are only subtypes of themselves
+impl<I: Index> Subtype<TVar<I>> for TVar<I> {}
+
+impl<S: Type, T: Type> Subtype<Exists<T>> for Exists<S> where S: Subtype<T> {}
+
+// S-Rcd, which combines width, depth and permutation: every field of the
+// supertype must be a field of the subtype, with a subtype of its type
+trait FieldsSub<Fs: FieldTys>: FieldTys {}
+impl<Fs: FieldTys> FieldsSub<Fs> for FieldTyNil {}
+impl<Fs: FieldTys, L: Label, Tp: Type, Tl: FieldTys, S: Type> FieldsSub<Fs>
+    for FieldTyCons<L, Tp, Tl>
+where
+    Fs: FieldType<L, Res = S>,
+    S: Subtype<Tp>,
+    Tl: FieldsSub<Fs>,
+{
+}
+impl<Fs: FieldTys, Gs: FieldTys> Subtype<RecordTy<Gs>> for RecordTy<Fs> where Gs: FieldsSub<Fs> {}
+
+// S-Variant, dual to records: every case of the subtype must be a case of
+// the supertype
+impl<Cs: FieldTys, Ds: FieldTys> Subtype<VariantTy<Ds>> for VariantTy<Cs> where Cs: CasesSub<Ds> {}
+trait CasesSub<Ds: FieldTys>: FieldTys {}
+impl<Ds: FieldTys> CasesSub<Ds> for FieldTyNil {}
+impl<Ds: FieldTys, L: Label, Tp: Type, Tl: FieldTys, U: Type> CasesSub<Ds>
+    for FieldTyCons<L, Tp, Tl>
+where
+    Ds: FieldType<L, Res = U>,
+    Tp: Subtype<U>,
+    Tl: CasesSub<Ds>,
+{
+}
+
+// Typing with subsumption, for the pure fragment of the language with
+// records. The rules are the ones of WellTyped, except that wherever a type
+// is expected, any of its subtypes is accepted.
+pub trait SubTyped<E: Env> {
+    type Tp: Type;
+}
+
+// T-True
+impl<E: Env> SubTyped<E> for True {
+    type Tp = Bool;
+}
+
+// T-False
+impl<E: Env> SubTyped<E> for False {
+    type Tp = Bool;
+}
+
+// T-Zero
+impl<E: Env> SubTyped<E> for Zero {
+    type Tp = Nat;
+}
+
+// T-Unit
+impl<E: Env> SubTyped<E> for Triv {
+    type Tp = Unit;
+}
+
+// T-Succ
+impl<E: Env, T: Term> SubTyped<E> for Succ<T>
+where
+    T: SubTyped<E, Tp = Nat>,
+{
+    type Tp = Nat;
+}
+
+// T-IsZero
+impl<E: Env, T: Term> SubTyped<E> for IsZero<T>
+where
+    T: SubTyped<E, Tp = Nat>,
+{
+    type Tp = Bool;
+}
+
+// T-Pred
+impl<E: Env, T: Term> SubTyped<E> for Pred<T>
+where
+    T: SubTyped<E, Tp = Nat>,
+{
+    type Tp = Nat;
+}
+
+// T-Var, looking up a variable is the same as without subtyping
+impl<E: Env, I: Index, Tp: Type> SubTyped<E> for Var<I>
+where
+    Var<I>: WellTyped<E, Tp = Tp>,
+{
+    type Tp = Tp;
+}
+
+// T-Lambda
+impl<E: Env, Tp: Type, T: Term> SubTyped<E> for Lam<Tp, T>
+where
+    T: SubTyped<TyCons<Tp, E>>,
+{
+    type Tp = Arrow<Tp, <T as SubTyped<TyCons<Tp, E>>>::Tp>;
+}
+
+// T-App, the argument may have any subtype of the parameter type
+impl<E: Env, ITp: Type, OTp: Type, ATp: Type, F: Term, T: Term> SubTyped<E> for App<F, T>
+where
+    F: SubTyped<E, Tp = Arrow<ITp, OTp>>,
+    T: SubTyped<E, Tp = ATp>,
+    ATp: Subtype<ITp>,
+{
+    type Tp = OTp;
+}
+
+// T-If, the type of the else branch must be a subtype of the then branch
+impl<E: Env, Tp: Type, ETp: Type, Cond: Term, Then: Term, Else: Term> SubTyped<E>
+    for If<Cond, Then, Else>
+where
+    Cond: SubTyped<E, Tp = Bool>,
+    Then: SubTyped<E, Tp = Tp>,
+    Else: SubTyped<E, Tp = ETp>,
+    ETp: Subtype<Tp>,
+{
+    type Tp = Tp;
+}
+
+// T-Let
+impl<E: Env, Tp: Type, T: Term, Body: Term> SubTyped<E> for Let<T, Body>
+where
+    T: SubTyped<E, Tp = Tp>,
+    Body: SubTyped<TyCons<Tp, E>>,
+{
+    type Tp = <Body as SubTyped<TyCons<Tp, E>>>::Tp;
+}
+
+// T-Fix
+impl<E: Env, Tp: Type, T: Term> SubTyped<E> for Fix<T>
+where
+    T: SubTyped<E, Tp = Arrow<Tp, Tp>>,
+{
+    type Tp = Tp;
+}
+
+// T-Ann, an ascription can forget part of the type of a term
+impl<E: Env, Tp: Type, S: Type, T: Term> SubTyped<E> for Ann<T, Tp>
+where
+    T: SubTyped<E, Tp = S>,
+    S: Subtype<Tp>,
+{
+    type Tp = Tp;
+}
+
+// T-Record
+trait FieldsSubTyped<E: Env>: FieldList {
+    type Tys: FieldTys;
+}
+impl<E: Env> FieldsSubTyped<E> for FieldNil {
+    type Tys = FieldTyNil;
+}
+impl<E: Env, L: Label, T: Term, Tl: FieldList, Tp: Type, TlTys: FieldTys> FieldsSubTyped<E>
+    for FieldCons<L, T, Tl>
+where
+    T: SubTyped<E, Tp = Tp>,
+    Tl: FieldsSubTyped<E, Tys = TlTys>,
+{
+    type Tys = FieldTyCons<L, Tp, TlTys>;
+}
+impl<E: Env, Fs: FieldList, Tys: FieldTys> SubTyped<E> for Record<Fs>
+where
+    Fs: FieldsSubTyped<E, Tys = Tys>,
+{
+    type Tp = RecordTy<Tys>;
+}
+
+// T-Proj
+impl<E: Env, T: Term, L: Label, Tys: FieldTys, Tp: Type> SubTyped<E> for Proj<T, L>
+where
+    T: SubTyped<E, Tp = RecordTy<Tys>>,
+    Tys: FieldType<L, Res = Tp>,
+{
+    type Tp = Tp;
+}
+
+/// Util function to check that S is a subtype of T
+pub fn is_subtype<S: Subtype<T>, T: Type>() {}
+
+/// Util function to type-check a closed term with subsumption
+pub fn subtype_checks<T>()
+where
+    T: Term,
+    T: SubTyped<EmptyEnv>,
+{
+}
+
+/// Util function to compute the type of T with subsumption
+pub fn subtype_of<T>() -> PhantomData<<T as SubTyped<EmptyEnv>>::Tp>
+where
+    T: Term,
+    T: SubTyped<EmptyEnv>,
+{
+    PhantomData
+}
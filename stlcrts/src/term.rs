@@ -7,6 +7,10 @@ pub struct False;
 impl Term for True {}
 impl Term for False {}
 
+/// The only value of type Unit
+pub struct Triv;
+impl Term for Triv {}
+
 pub struct Zero;
 pub struct Succ<T: Term>(PhantomData<T>);
 impl Term for Zero {}
@@ -45,6 +49,258 @@ impl<T: Term, Body: Term> Term for Let<T, Body> {}
 pub struct Fix<T: Term>(PhantomData<T>);
 impl<T: Term> Term for Fix<T> {}
 
+/// Primitive recursion on natural numbers, with
+/// NatRec<Z, S, 0> = Z and NatRec<Z, S, n + 1> = S n (NatRec<Z, S, n>)
+pub struct NatRec<Z: Term, S: Term, N: Term>(PhantomData<(Z, S, N)>);
+impl<Z: Term, S: Term, N: Term> Term for NatRec<Z, S, N> {}
+
+/// Raises an exception, whose payload is a natural number. It has no type
+/// on its own, so it must appear where a type is pushed in (for instance
+/// in the else branch of an if, or under an annotation)
+pub struct Raise<T: Term>(PhantomData<T>);
+impl<T: Term> Term for Raise<T> {}
+
+/// Evaluates T, and if it raises an exception evaluates the handler
+/// with the payload bound to index 0
+pub struct Try<T: Term, Handler: Term>(PhantomData<(T, Handler)>);
+impl<T: Term, Handler: Term> Term for Try<T, Handler> {}
+
+/// Allocates a new location in the store, initialized with T
+pub struct Ref<T: Term>(PhantomData<T>);
+impl<T: Term> Term for Ref<T> {}
+
+/// Reads the content of a location
+pub struct Deref<T: Term>(PhantomData<T>);
+impl<T: Term> Term for Deref<T> {}
+
+/// Writes T at location L
+pub struct Assign<L: Term, T: Term>(PhantomData<(L, T)>);
+impl<L: Term, T: Term> Term for Assign<L, T> {}
+
+/// A location in the store, they only appear during evaluation.
+/// Like variables they are indices, but counted from the first allocation.
+pub struct Loc<I: Index>(PhantomData<I>);
+impl<I: Index> Term for Loc<I> {}
+
+/// Prints the value of T, only the evaluator with output (`eval_io`) supports it
+pub struct Print<T: Term>(PhantomData<T>);
+impl<T: Term> Term for Print<T> {}
+
+/// Calls T with the current continuation, only the abstract machine (`run`) supports it
+pub struct CallCC<T: Term>(PhantomData<T>);
+impl<T: Term> Term for CallCC<T> {}
+
+/// Passes the value V to the continuation K, discarding the current one
+pub struct Throw<K: Term, V: Term>(PhantomData<(K, V)>);
+impl<K: Term, V: Term> Term for Throw<K, V> {}
+
+/// A continuation captured by CallCC, it only appears during evaluation
+pub struct ContV<K: Stack>(PhantomData<K>);
+impl<K: Stack> Term for ContV<K> {}
+
+// Labels of record fields, the name of the field spelled in binary so that
+// two labels can be compared. `stlc!` generates them from the identifiers.
+pub trait Label {}
+pub struct LEnd;
+pub struct L0<Tl: Label>(PhantomData<Tl>);
+pub struct L1<Tl: Label>(PhantomData<Tl>);
+impl Label for LEnd {}
+impl<Tl: Label> Label for L0<Tl> {}
+impl<Tl: Label> Label for L1<Tl> {}
+
+// Label equality, Res is True if both labels are the same and False otherwise
+pub trait LabelEq<L: Label>: Label {
+    type Res: Term;
+}
+impl LabelEq<LEnd> for LEnd {
+    type Res = True;
+}
+impl<Tl: Label> LabelEq<L0<Tl>> for LEnd {
+    type Res = False;
+}
+impl<Tl: Label> LabelEq<L1<Tl>> for LEnd {
+    type Res = False;
+}
+impl<Tl: Label> LabelEq<LEnd> for L0<Tl> {
+    type Res = False;
+}
+impl<Tl: Label> LabelEq<LEnd> for L1<Tl> {
+    type Res = False;
+}
+impl<Tl: Label, Tl2: Label> LabelEq<L1<Tl2>> for L0<Tl> {
+    type Res = False;
+}
+impl<Tl: Label, Tl2: Label> LabelEq<L0<Tl2>> for L1<Tl> {
+    type Res = False;
+}
+impl<Tl: Label, Tl2: Label, R: Term> LabelEq<L0<Tl2>> for L0<Tl>
+where
+    Tl: LabelEq<Tl2, Res = R>,
+{
+    type Res = R;
+}
+impl<Tl: Label, Tl2: Label, R: Term> LabelEq<L1<Tl2>> for L1<Tl>
+where
+    Tl: LabelEq<Tl2, Res = R>,
+{
+    type Res = R;
+}
+
+/// The fields of a record, in the order they are written
+pub trait FieldList {}
+pub struct FieldNil;
+impl FieldList for FieldNil {}
+pub struct FieldCons<L: Label, T: Term, Tl: FieldList>(PhantomData<(L, T, Tl)>);
+impl<L: Label, T: Term, Tl: FieldList> FieldList for FieldCons<L, T, Tl> {}
+
+/// A record, its fields are evaluated from left to right
+pub struct Record<Fs: FieldList>(PhantomData<Fs>);
+impl<Fs: FieldList> Term for Record<Fs> {}
+
+/// Projection of the field labelled L out of the record T
+pub struct Proj<T: Term, L: Label>(PhantomData<(T, L)>);
+impl<T: Term, L: Label> Term for Proj<T, L> {}
+
+/// Injects T into the variant type Tp, as the case labelled L
+pub struct Tag<L: Label, T: Term, Tp: Type>(PhantomData<(L, T, Tp)>);
+impl<L: Label, T: Term, Tp: Type> Term for Tag<L, T, Tp> {}
+
+/// The branches of a match, each one binds the payload of its case to index 0
+pub trait BranchList {}
+pub struct BranchNil;
+impl BranchList for BranchNil {}
+pub struct BranchCons<L: Label, T: Term, Tl: BranchList>(PhantomData<(L, T, Tl)>);
+impl<L: Label, T: Term, Tl: BranchList> BranchList for BranchCons<L, T, Tl> {}
+
+// Finds the branch handling the case labelled L
+pub trait Branch<L: Label>: BranchList {
+    type Res: Term;
+}
+trait BranchIf<L: Label, T: Term, Tl: BranchList> {
+    type Res: Term;
+}
+impl<L: Label, T: Term, Tl: BranchList> BranchIf<L, T, Tl> for True {
+    type Res = T;
+}
+impl<L: Label, T: Term, Tl: BranchList, R: Term> BranchIf<L, T, Tl> for False
+where
+    Tl: Branch<L, Res = R>,
+{
+    type Res = R;
+}
+impl<L: Label, L1: Label, T: Term, Tl: BranchList, B: Term, R: Term> Branch<L>
+    for BranchCons<L1, T, Tl>
+where
+    L1: LabelEq<L, Res = B>,
+    B: BranchIf<L, T, Tl, Res = R>,
+{
+    type Res = R;
+}
+
+/// Case analysis on a variant, the branch of the case of T is taken
+pub struct Match<T: Term, Bs: BranchList>(PhantomData<(T, Bs)>);
+impl<T: Term, Bs: BranchList> Term for Match<T, Bs> {}
+
+/// The empty list of elements of type Tp
+pub struct Nil<Tp: Type>(PhantomData<Tp>);
+impl<Tp: Type> Term for Nil<Tp> {}
+
+/// Empty list whose element type is omitted, like CLam it only type-checks
+/// when a list type is pushed in, for instance as the tail of a Cons.
+pub struct CNil;
+impl Term for CNil {}
+
+pub struct Cons<H: Term, T: Term>(PhantomData<(H, T)>);
+impl<H: Term, T: Term> Term for Cons<H, T> {}
+
+/// Case analysis on a list, ConsBranch binds the head to index 1 and the tail to index 0
+pub struct ListCase<L: Term, NilBranch: Term, ConsBranch: Term>(
+    PhantomData<(L, NilBranch, ConsBranch)>,
+);
+impl<L: Term, NilBranch: Term, ConsBranch: Term> Term for ListCase<L, NilBranch, ConsBranch> {}
+
+/// Packs T as a term of the existential type ExTp, T has the type of its
+/// body with HiddenTp for the type variable, which is then hidden
+pub struct Pack<HiddenTp: Type, T: Term, ExTp: Type>(PhantomData<(HiddenTp, T, ExTp)>);
+impl<HiddenTp: Type, T: Term, ExTp: Type> Term for Pack<HiddenTp, T, ExTp> {}
+
+/// Opens the package T in Body, its term is bound to index 0
+/// and its hidden type to the type variable TVar<I0>
+pub struct Unpack<T: Term, Body: Term>(PhantomData<(T, Body)>);
+impl<T: Term, Body: Term> Term for Unpack<T, Body> {}
+
+/// Casts T from the type From to the type To, checking at runtime that the
+/// value fits. Casts are not written by hand, they are inserted by `CastInsert`
+pub struct Cast<T: Term, From: Type, To: Type>(PhantomData<(T, From, To)>);
+impl<T: Term, From: Type, To: Type> Term for Cast<T, From, To> {}
+
+/// The outcome of a failed cast
+pub struct Blame;
+impl Term for Blame {}
+
+/// A group of mutually recursive functions, each with its declared type.
+/// Every function is abstracted over the whole group, the first function
+/// being bound by the outermost lambda.
+pub trait FnList {}
+pub struct FnNil;
+impl FnList for FnNil {}
+pub struct FnCons<Tp: Type, T: Term, Tl: FnList>(PhantomData<(Tp, T, Tl)>);
+impl<Tp: Type, T: Term, Tl: FnList> FnList for FnCons<Tp, T, Tl> {}
+
+/// The K-th function of a mutually recursive group
+pub struct FixN<Fs: FnList, K: Index>(PhantomData<(Fs, K)>);
+impl<Fs: FnList, K: Index> Term for FixN<Fs, K> {}
+
+/// Type ascription, T must check against Tp. It is erased during evaluation.
+pub struct Ann<T: Term, Tp: Type>(PhantomData<(T, Tp)>);
+impl<T: Term, Tp: Type> Term for Ann<T, Tp> {}
+
+/// Lambda whose parameter type is omitted, it only type-checks
+/// when an arrow type is pushed in, for instance by an `Ann`.
+pub struct CLam<T: Term>(PhantomData<T>);
+impl<T: Term> Term for CLam<T> {}
+
+/// Lambda of the untyped lambda calculus, what `Erase` turns every
+/// abstraction into. Unlike CLam it never type-checks.
+pub struct ULam<T: Term>(PhantomData<T>);
+impl<T: Term> Term for ULam<T> {}
+
+// Terms with named variables, the names being labels declared with names!.
+// They are turned into the terms above by ToDeBruijn.
+pub struct NVar<X: Label>(PhantomData<X>);
+impl<X: Label> Term for NVar<X> {}
+
+pub struct NLam<X: Label, Tp: Type, T: Term>(PhantomData<(X, Tp, T)>);
+impl<X: Label, Tp: Type, T: Term> Term for NLam<X, Tp, T> {}
+
+pub struct NLet<X: Label, T: Term, Body: Term>(PhantomData<(X, T, Body)>);
+impl<X: Label, T: Term, Body: Term> Term for NLet<X, T, Body> {}
+
+pub struct NCLam<X: Label, T: Term>(PhantomData<(X, T)>);
+impl<X: Label, T: Term> Term for NCLam<X, T> {}
+
+/// The handler binds the payload of the exception to X
+pub struct NTry<T: Term, X: Label, Handler: Term>(PhantomData<(T, X, Handler)>);
+impl<T: Term, X: Label, Handler: Term> Term for NTry<T, X, Handler> {}
+
+/// A branch of a Match binding the payload of its case to X, the branches
+/// of a match with named variables must all be NBranchCons
+pub struct NBranchCons<L: Label, X: Label, T: Term, Tl: BranchList>(PhantomData<(L, X, T, Tl)>);
+impl<L: Label, X: Label, T: Term, Tl: BranchList> BranchList for NBranchCons<L, X, T, Tl> {}
+
+/// ConsBranch binds the head to H and the tail to Tl
+pub struct NListCase<L: Term, NilBranch: Term, H: Label, Tl: Label, ConsBranch: Term>(
+    PhantomData<(L, NilBranch, H, Tl, ConsBranch)>,
+);
+impl<L: Term, NilBranch: Term, H: Label, Tl: Label, ConsBranch: Term> Term
+    for NListCase<L, NilBranch, H, Tl, ConsBranch>
+{
+}
+
+/// The term of the package is bound to X, its hidden type is still TVar<I0>
+pub struct NUnpack<X: Label, T: Term, Body: Term>(PhantomData<(X, T, Body)>);
+impl<X: Label, T: Term, Body: Term> Term for NUnpack<X, T, Body> {}
+
 pub trait Type {}
 
 pub struct Bool;
@@ -56,9 +312,73 @@ impl Type for Nat {}
 pub struct Arrow<T: Type, U: Type>(PhantomData<(T, U)>);
 impl<T: Type, U: Type> Type for Arrow<T, U> {}
 
+pub struct Unit;
+impl Type for Unit {}
+
+/// The supertype of every type, see `Subtype`
+pub struct Top;
+impl Type for Top {}
+
+/// The dynamic type of gradual typing, consistent with every type
+pub struct Dyn;
+impl Type for Dyn {}
+
+pub struct Cont<Tp: Type>(PhantomData<Tp>);
+impl<Tp: Type> Type for Cont<Tp> {}
+
+pub struct RefTy<Tp: Type>(PhantomData<Tp>);
+impl<Tp: Type> Type for RefTy<Tp> {}
+
+/// The types of the fields of a record, in the same order as the fields
+pub trait FieldTys {}
+pub struct FieldTyNil;
+impl FieldTys for FieldTyNil {}
+pub struct FieldTyCons<L: Label, Tp: Type, Tl: FieldTys>(PhantomData<(L, Tp, Tl)>);
+impl<L: Label, Tp: Type, Tl: FieldTys> FieldTys for FieldTyCons<L, Tp, Tl> {}
+
+pub struct RecordTy<Fs: FieldTys>(PhantomData<Fs>);
+impl<Fs: FieldTys> Type for RecordTy<Fs> {}
+
+pub struct List<Tp: Type>(PhantomData<Tp>);
+impl<Tp: Type> Type for List<Tp> {}
+
+/// Type variables, like term variables they are De Bruijn indices
+pub struct TVar<I: Index>(PhantomData<I>);
+impl<I: Index> Type for TVar<I> {}
+
+/// Existential type, binds the type variable TVar<I0> in Tp
+pub struct Exists<Tp: Type>(PhantomData<Tp>);
+impl<Tp: Type> Type for Exists<Tp> {}
+
+/// A variant type, its cases are listed the same way as the fields of a record
+pub struct VariantTy<Cs: FieldTys>(PhantomData<Cs>);
+impl<Cs: FieldTys> Type for VariantTy<Cs> {}
+
 // The environment for the typechecker, just a list of types
 pub trait Env {}
 pub struct EmptyEnv;
 impl Env for EmptyEnv {}
 pub struct TyCons<Tp: Type, Tl: Env>(PhantomData<(Tp, Tl)>);
 impl<Tp: Type, Tl: Env> Env for TyCons<Tp, Tl> {}
+
+/// A term together with the environment typing its free variables,
+/// this is what `stlc!` produces when given a `with` header.
+pub struct Open<E: Env, T: Term>(PhantomData<(E, T)>);
+impl<E: Env, T: Term> Term for Open<E, T> {}
+
+pub trait OpenTerm {
+    type Env: Env;
+    type Body: Term;
+}
+impl<E: Env, T: Term> OpenTerm for Open<E, T> {
+    type Env = E;
+    type Body = T;
+}
+
+// The evaluation context of the abstract machine, a stack of frames
+pub trait Frame {}
+pub trait Stack {}
+pub struct Halt;
+impl Stack for Halt {}
+pub struct Push<F: Frame, K: Stack>(PhantomData<(F, K)>);
+impl<F: Frame, K: Stack> Stack for Push<F, K> {}
@@ -1,107 +1,1063 @@
 use crate::term::*;
+use std::marker::PhantomData;
+use tyshiftby1::TyShiftBy1;
+use tyshiftbyneg1::TyShiftByNeg1;
+use tysubst::TySubst;
 
-// A term T implements WellTyped<E> if it is well-typed under environment E.
+// A term T implements WellTyped<E, St> if it is well-typed under environment E
+// and store typing St, which gives the type of each allocated location.
 // Furthermore its type is Tp
-pub trait WellTyped<E: Env> {
+pub trait WellTyped<E: Env, St: Env = EmptyEnv> {
     type Tp: Type;
 }
 
 // T-True
-impl<E: Env> WellTyped<E> for True {
+impl<E: Env, St: Env> WellTyped<E, St> for True {
     type Tp = Bool;
 }
 
 // T-False
-impl<E: Env> WellTyped<E> for False {
+impl<E: Env, St: Env> WellTyped<E, St> for False {
     type Tp = Bool;
 }
 
 // T-Zero
-impl<E: Env> WellTyped<E> for Zero {
+impl<E: Env, St: Env> WellTyped<E, St> for Zero {
     type Tp = Nat;
 }
 
+// T-Unit
+impl<E: Env, St: Env> WellTyped<E, St> for Triv {
+    type Tp = Unit;
+}
+
 // T-Succ
-impl<E: Env, T: Term> WellTyped<E> for Succ<T>
+impl<E: Env, St: Env, T: Term> WellTyped<E, St> for Succ<T>
 where
-    T: WellTyped<E, Tp = Nat>,
+    T: WellTyped<E, St, Tp = Nat>,
 {
     type Tp = Nat;
 }
 
 // T-IsZero
-impl<E: Env, T: Term> WellTyped<E> for IsZero<T>
+impl<E: Env, St: Env, T: Term> WellTyped<E, St> for IsZero<T>
 where
-    T: WellTyped<E, Tp = Nat>,
+    T: WellTyped<E, St, Tp = Nat>,
 {
     type Tp = Bool;
 }
 
 // T-Pred
-impl<E: Env, T: Term> WellTyped<E> for Pred<T>
+impl<E: Env, St: Env, T: Term> WellTyped<E, St> for Pred<T>
 where
-    T: WellTyped<E, Tp = Nat>,
+    T: WellTyped<E, St, Tp = Nat>,
 {
     type Tp = Nat;
 }
 
 // T-VarSucc
-impl<N: Index, E: Env, Tp: Type> WellTyped<TyCons<Tp, E>> for Var<ISucc<N>>
+impl<N: Index, E: Env, St: Env, Tp: Type> WellTyped<TyCons<Tp, E>, St> for Var<ISucc<N>>
 where
-    Var<N>: WellTyped<E>,
+    Var<N>: WellTyped<E, St>,
 {
-    type Tp = <Var<N> as WellTyped<E>>::Tp;
+    type Tp = <Var<N> as WellTyped<E, St>>::Tp;
 }
 
 // T-Var0
-impl<E: Env, Tp: Type> WellTyped<TyCons<Tp, E>> for Var<I0> {
+impl<E: Env, St: Env, Tp: Type> WellTyped<TyCons<Tp, E>, St> for Var<I0> {
     type Tp = Tp;
 }
 
 // T-Lambda
-impl<E: Env, Tp: Type, T: Term> WellTyped<E> for Lam<Tp, T>
+impl<E: Env, St: Env, Tp: Type, T: Term> WellTyped<E, St> for Lam<Tp, T>
 where
-    T: WellTyped<TyCons<Tp, E>>,
+    T: WellTyped<TyCons<Tp, E>, St>,
 {
-    type Tp = Arrow<Tp, <T as WellTyped<TyCons<Tp, E>>>::Tp>;
+    type Tp = Arrow<Tp, <T as WellTyped<TyCons<Tp, E>, St>>::Tp>;
 }
 
-// T-App
-impl<E: Env, ITp: Type, OTp: Type, T: Term, F: Term> WellTyped<E> for App<F, T>
+// T-App, the argument is checked against the parameter type
+impl<E: Env, St: Env, ITp: Type, OTp: Type, T: Term, F: Term> WellTyped<E, St> for App<F, T>
 where
-    T: WellTyped<E, Tp = ITp>,
-    F: WellTyped<E, Tp = Arrow<ITp, OTp>>,
+    F: WellTyped<E, St, Tp = Arrow<ITp, OTp>>,
+    T: Checks<E, ITp, St>,
 {
     type Tp = OTp;
 }
 
-// T-If
-impl<E: Env, Tp: Type, Cond: Term, Then: Term, Else: Term> WellTyped<E> for If<Cond, Then, Else>
+// T-If, one branch is checked against the type of the other one, which is
+// the then branch unless it can only be checked
+impl<E: Env, St: Env, Tp: Type, Cond: Term, Then: Term, Else: Term, S> WellTyped<E, St>
+    for If<Cond, Then, Else>
+where
+    Cond: WellTyped<E, St, Tp = Bool>,
+    Then: Side<Res = S>,
+    (Then, Else): Branches<E, St, S, Tp = Tp>,
+{
+    type Tp = Tp;
+}
+
+// The branch of an if which synthesizes its type, given by its then branch
+pub(crate) struct ThenSide;
+pub(crate) struct ElseSide;
+trait Branches<E: Env, St: Env, S> {
+    type Tp: Type;
+}
+impl<E: Env, St: Env, Tp: Type, Then: Term, Else: Term> Branches<E, St, ThenSide> for (Then, Else)
+where
+    Then: WellTyped<E, St, Tp = Tp>,
+    Else: Checks<E, Tp, St>,
+{
+    type Tp = Tp;
+}
+impl<E: Env, St: Env, Tp: Type, Then: Term, Else: Term> Branches<E, St, ElseSide> for (Then, Else)
 where
-    Cond: WellTyped<E, Tp = Bool>,
-    Then: WellTyped<E, Tp = Tp>,
-    Else: WellTyped<E, Tp = Tp>,
+    Then: Checks<E, Tp, St>,
+    Else: WellTyped<E, St, Tp = Tp>,
 {
     type Tp = Tp;
 }
 
+pub(crate) trait Side {
+    type Res;
+}
+// The terms which can only be checked
+impl<T: Term> Side for Raise<T> {
+    type Res = ElseSide;
+}
+impl<K: Term, V: Term> Side for Throw<K, V> {
+    type Res = ElseSide;
+}
+impl<T: Term> Side for CLam<T> {
+    type Res = ElseSide;
+}
+impl Side for CNil {
+    type Res = ElseSide;
+}
+// Every other term
+impl Side for True {
+    type Res = ThenSide;
+}
+impl Side for False {
+    type Res = ThenSide;
+}
+impl Side for Zero {
+    type Res = ThenSide;
+}
+impl Side for Triv {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for Succ<T> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for IsZero<T> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for Pred<T> {
+    type Res = ThenSide;
+}
+impl<I: Index> Side for Var<I> {
+    type Res = ThenSide;
+}
+impl<Tp: Type, T: Term> Side for Lam<Tp, T> {
+    type Res = ThenSide;
+}
+impl<F: Term, T: Term> Side for App<F, T> {
+    type Res = ThenSide;
+}
+impl<C: Term, T: Term, F: Term> Side for If<C, T, F> {
+    type Res = ThenSide;
+}
+impl<T: Term, Body: Term> Side for Let<T, Body> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for Fix<T> {
+    type Res = ThenSide;
+}
+impl<Fs: FnList, K: Index> Side for FixN<Fs, K> {
+    type Res = ThenSide;
+}
+impl<Z: Term, S: Term, N: Term> Side for NatRec<Z, S, N> {
+    type Res = ThenSide;
+}
+impl<T: Term, Handler: Term> Side for Try<T, Handler> {
+    type Res = ThenSide;
+}
+impl<T: Term, Tp: Type> Side for Ann<T, Tp> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for Ref<T> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for Deref<T> {
+    type Res = ThenSide;
+}
+impl<L: Term, T: Term> Side for Assign<L, T> {
+    type Res = ThenSide;
+}
+impl<I: Index> Side for Loc<I> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for Print<T> {
+    type Res = ThenSide;
+}
+impl<T: Term> Side for CallCC<T> {
+    type Res = ThenSide;
+}
+impl<Fs: FieldList> Side for Record<Fs> {
+    type Res = ThenSide;
+}
+impl<T: Term, L: Label> Side for Proj<T, L> {
+    type Res = ThenSide;
+}
+impl<L: Label, T: Term, Tp: Type> Side for Tag<L, T, Tp> {
+    type Res = ThenSide;
+}
+impl<T: Term, Bs: BranchList> Side for Match<T, Bs> {
+    type Res = ThenSide;
+}
+impl<Tp: Type> Side for Nil<Tp> {
+    type Res = ThenSide;
+}
+impl<H: Term, T: Term> Side for Cons<H, T> {
+    type Res = ThenSide;
+}
+impl<L: Term, N: Term, C: Term> Side for ListCase<L, N, C> {
+    type Res = ThenSide;
+}
+impl<H: Type, T: Term, ExTp: Type> Side for Pack<H, T, ExTp> {
+    type Res = ThenSide;
+}
+impl<T: Term, Body: Term> Side for Unpack<T, Body> {
+    type Res = ThenSide;
+}
+
 // T-Let
-impl<E: Env, Tp: Type, T: Term, Body: Term> WellTyped<E> for Let<T, Body>
+impl<E: Env, St: Env, Tp: Type, T: Term, Body: Term> WellTyped<E, St> for Let<T, Body>
 where
-    Body: WellTyped<TyCons<Tp, E>>,
-    T: WellTyped<E, Tp = Tp>,
+    Body: WellTyped<TyCons<Tp, E>, St>,
+    T: WellTyped<E, St, Tp = Tp>,
 {
-    type Tp = <Body as WellTyped<TyCons<Tp, E>>>::Tp;
+    type Tp = <Body as WellTyped<TyCons<Tp, E>, St>>::Tp;
 }
 
 // T-Fix
-impl<E: Env, Tp: Type, T: Term> WellTyped<E> for Fix<T>
+impl<E: Env, St: Env, Tp: Type, T: Term> WellTyped<E, St> for Fix<T>
+where
+    T: WellTyped<E, St, Tp = Arrow<Tp, Tp>>,
+{
+    type Tp = Tp;
+}
+
+// T-NatRec
+impl<E: Env, St: Env, Tp: Type, Z: Term, S: Term, N: Term> WellTyped<E, St> for NatRec<Z, S, N>
+where
+    Z: WellTyped<E, St, Tp = Tp>,
+    S: Checks<E, Arrow<Nat, Arrow<Tp, Tp>>, St>,
+    N: WellTyped<E, St, Tp = Nat>,
+{
+    type Tp = Tp;
+}
+
+// T-Try, raise has no type on its own so it can only be checked
+impl<E: Env, St: Env, Tp: Type, T: Term, Handler: Term> WellTyped<E, St> for Try<T, Handler>
+where
+    T: WellTyped<E, St, Tp = Tp>,
+    Handler: Checks<TyCons<Nat, E>, Tp, St>,
+{
+    type Tp = Tp;
+}
+
+// T-Ref
+impl<E: Env, St: Env, Tp: Type, T: Term> WellTyped<E, St> for Ref<T>
+where
+    T: WellTyped<E, St, Tp = Tp>,
+{
+    type Tp = RefTy<Tp>;
+}
+
+// T-Deref
+impl<E: Env, St: Env, Tp: Type, T: Term> WellTyped<E, St> for Deref<T>
 where
-    T: WellTyped<E, Tp = Arrow<Tp, Tp>>,
+    T: WellTyped<E, St, Tp = RefTy<Tp>>,
 {
     type Tp = Tp;
 }
 
+// T-Assign
+impl<E: Env, St: Env, Tp: Type, L: Term, T: Term> WellTyped<E, St> for Assign<L, T>
+where
+    L: WellTyped<E, St, Tp = RefTy<Tp>>,
+    T: Checks<E, Tp, St>,
+{
+    type Tp = Unit;
+}
+
+// T-Loc, the store typing is a list of types just like an environment
+// so we can reuse the rules for variables to find the type of a location
+impl<E: Env, St: Env, Tp: Type, I: Index> WellTyped<E, St> for Loc<I>
+where
+    Var<I>: WellTyped<St, EmptyEnv, Tp = Tp>,
+{
+    type Tp = RefTy<Tp>;
+}
+
+// T-Print, any value can be printed
+impl<E: Env, St: Env, T: Term> WellTyped<E, St> for Print<T>
+where
+    T: WellTyped<E, St>,
+{
+    type Tp = Unit;
+}
+
+// T-CallCC
+impl<E: Env, St: Env, Tp: Type, T: Term> WellTyped<E, St> for CallCC<T>
+where
+    T: WellTyped<E, St, Tp = Arrow<Cont<Tp>, Tp>>,
+{
+    type Tp = Tp;
+}
+
+// For T-FixN we need a few helpers on groups of functions. Curry<R>
+// builds the type Tp0 -> ... -> Tpn -> R of a function abstracted over the
+// whole group, GroupTyped checks that every function has its declared type
+// once abstracted, and NthType finds the type of the K-th function.
+trait Curry<R: Type> {
+    type Tp: Type;
+}
+impl<R: Type> Curry<R> for FnNil {
+    type Tp = R;
+}
+impl<R: Type, Tp: Type, T: Term, Tl: FnList, TlTp: Type> Curry<R> for FnCons<Tp, T, Tl>
+where
+    Tl: Curry<R, Tp = TlTp>,
+{
+    type Tp = Arrow<Tp, TlTp>;
+}
+
+trait GroupTyped<E: Env, St: Env, G: FnList> {}
+impl<E: Env, St: Env, G: FnList> GroupTyped<E, St, G> for FnNil {}
+impl<E: Env, St: Env, G: FnList, Tp: Type, T: Term, Tl: FnList, FTp: Type> GroupTyped<E, St, G>
+    for FnCons<Tp, T, Tl>
+where
+    G: Curry<Tp, Tp = FTp>,
+    T: WellTyped<E, St, Tp = FTp>,
+    Tl: GroupTyped<E, St, G>,
+{
+}
+
+trait NthType<K: Index> {
+    type Tp: Type;
+}
+impl<Tp: Type, T: Term, Tl: FnList> NthType<I0> for FnCons<Tp, T, Tl> {
+    type Tp = Tp;
+}
+impl<K: Index, Tp: Type, T: Term, Tl: FnList, R: Type> NthType<ISucc<K>> for FnCons<Tp, T, Tl>
+where
+    Tl: NthType<K, Tp = R>,
+{
+    type Tp = R;
+}
+
+// T-FixN
+impl<E: Env, St: Env, Fs: FnList, K: Index, Tp: Type> WellTyped<E, St> for FixN<Fs, K>
+where
+    Fs: GroupTyped<E, St, Fs>,
+    Fs: NthType<K, Tp = Tp>,
+{
+    type Tp = Tp;
+}
+
+// T-Ann
+impl<E: Env, St: Env, Tp: Type, T: Term> WellTyped<E, St> for Ann<T, Tp>
+where
+    T: Checks<E, Tp, St>,
+{
+    type Tp = Tp;
+}
+
+// T-Record, each field is typed in order
+trait FieldsTyped<E: Env, St: Env>: FieldList {
+    type Tys: FieldTys;
+}
+impl<E: Env, St: Env> FieldsTyped<E, St> for FieldNil {
+    type Tys = FieldTyNil;
+}
+impl<E: Env, St: Env, L: Label, T: Term, Tl: FieldList, Tp: Type, TlTys: FieldTys>
+    FieldsTyped<E, St> for FieldCons<L, T, Tl>
+where
+    T: WellTyped<E, St, Tp = Tp>,
+    Tl: FieldsTyped<E, St, Tys = TlTys>,
+{
+    type Tys = FieldTyCons<L, Tp, TlTys>;
+}
+impl<E: Env, St: Env, Fs: FieldList, Tys: FieldTys> WellTyped<E, St> for Record<Fs>
+where
+    Fs: FieldsTyped<E, St, Tys = Tys>,
+{
+    type Tp = RecordTy<Tys>;
+}
+
+// For T-Proj we look for the type of the field labelled L. Like for E-If,
+// the result of the label comparison selects what to do with FieldTypeIf.
+pub(crate) trait FieldType<L: Label>: FieldTys {
+    type Res: Type;
+}
+trait FieldTypeIf<L: Label, Tp: Type, Tl: FieldTys> {
+    type Res: Type;
+}
+impl<L: Label, Tp: Type, Tl: FieldTys> FieldTypeIf<L, Tp, Tl> for True {
+    type Res = Tp;
+}
+impl<L: Label, Tp: Type, Tl: FieldTys, R: Type> FieldTypeIf<L, Tp, Tl> for False
+where
+    Tl: FieldType<L, Res = R>,
+{
+    type Res = R;
+}
+impl<L: Label, L1: Label, Tp: Type, Tl: FieldTys, B: Term, R: Type> FieldType<L>
+    for FieldTyCons<L1, Tp, Tl>
+where
+    L1: LabelEq<L, Res = B>,
+    B: FieldTypeIf<L, Tp, Tl, Res = R>,
+{
+    type Res = R;
+}
+
+// T-Proj
+impl<E: Env, St: Env, T: Term, L: Label, Tys: FieldTys, Tp: Type> WellTyped<E, St> for Proj<T, L>
+where
+    T: WellTyped<E, St, Tp = RecordTy<Tys>>,
+    Tys: FieldType<L, Res = Tp>,
+{
+    type Tp = Tp;
+}
+
+// T-Tag, the payload is checked against the type of its case
+impl<E: Env, St: Env, L: Label, T: Term, Cs: FieldTys, Tp: Type> WellTyped<E, St>
+    for Tag<L, T, VariantTy<Cs>>
+where
+    Cs: FieldType<L, Res = Tp>,
+    T: Checks<E, Tp, St>,
+{
+    type Tp = VariantTy<Cs>;
+}
+
+// For T-Match, BranchesCheck checks that for every case there is a branch
+// of type Tp, with the payload of the case bound. Since the macro rejects
+// duplicate labels, having as many branches as cases means that there are
+// no other branches, so the match is exhaustive and has no dead branch.
+trait BranchesCheck<E: Env, St: Env, Bs: BranchList, Tp: Type>: FieldTys {}
+impl<E: Env, St: Env, Bs: BranchList, Tp: Type> BranchesCheck<E, St, Bs, Tp> for FieldTyNil {}
+impl<E: Env, St: Env, Bs: BranchList, Tp: Type, L: Label, CTp: Type, Tl: FieldTys, B: Term>
+    BranchesCheck<E, St, Bs, Tp> for FieldTyCons<L, CTp, Tl>
+where
+    Bs: Branch<L, Res = B>,
+    B: Checks<TyCons<CTp, E>, Tp, St>,
+    Tl: BranchesCheck<E, St, Bs, Tp>,
+{
+}
+trait SameLength<Cs: FieldTys>: BranchList {}
+impl SameLength<FieldTyNil> for BranchNil {}
+impl<L: Label, T: Term, Tl: BranchList, L1: Label, Tp: Type, Cs: FieldTys>
+    SameLength<FieldTyCons<L1, Tp, Cs>> for BranchCons<L, T, Tl>
+where
+    Tl: SameLength<Cs>,
+{
+}
+
+// T-Match, the type of the match is the one of the branch for the first
+// case, the other branches are checked against it
+impl<E: Env, St: Env, T: Term, Bs: BranchList, L: Label, CTp: Type, Cs: FieldTys, B: Term, Tp: Type>
+    WellTyped<E, St> for Match<T, Bs>
+where
+    T: WellTyped<E, St, Tp = VariantTy<FieldTyCons<L, CTp, Cs>>>,
+    Bs: Branch<L, Res = B>,
+    B: WellTyped<TyCons<CTp, E>, St, Tp = Tp>,
+    FieldTyCons<L, CTp, Cs>: BranchesCheck<E, St, Bs, Tp>,
+    Bs: SameLength<FieldTyCons<L, CTp, Cs>>,
+{
+    type Tp = Tp;
+}
+
+// T-Nil
+impl<E: Env, St: Env, Tp: Type> WellTyped<E, St> for Nil<Tp> {
+    type Tp = List<Tp>;
+}
+
+// T-Cons, the type of the elements is given by the head
+impl<E: Env, St: Env, Tp: Type, H: Term, T: Term> WellTyped<E, St> for Cons<H, T>
+where
+    H: WellTyped<E, St, Tp = Tp>,
+    T: Checks<E, List<Tp>, St>,
+{
+    type Tp = List<Tp>;
+}
+
+// T-ListCase, the cons branch is checked against the type of the nil branch
+impl<E: Env, St: Env, Tp: Type, ETp: Type, L: Term, NilBranch: Term, ConsBranch: Term>
+    WellTyped<E, St> for ListCase<L, NilBranch, ConsBranch>
+where
+    L: WellTyped<E, St, Tp = List<ETp>>,
+    NilBranch: WellTyped<E, St, Tp = Tp>,
+    ConsBranch: Checks<TyCons<List<ETp>, TyCons<ETp, E>>, Tp, St>,
+{
+    type Tp = Tp;
+}
+
+// Shifting and substitution of type variables in types work the same way as
+// for terms in the evaluator. TyMap<F, C> rebuilds a type, counting in C the
+// Exists it went under, and hands each type variable to the action F with
+// MapTyVar. A new type constructor only needs a TyMap impl.
+pub(crate) mod tymap {
+    use super::*;
+
+    pub trait TyMap<F, C: Index> {
+        type Res: Type;
+    }
+    // The action F on the type variable TVar<I>, under C binders
+    pub trait MapTyVar<I: Index, C: Index> {
+        type Res: Type;
+    }
+
+    impl<F, C: Index> TyMap<F, C> for Bool {
+        type Res = Bool;
+    }
+    impl<F, C: Index> TyMap<F, C> for Nat {
+        type Res = Nat;
+    }
+    impl<F, C: Index> TyMap<F, C> for Unit {
+        type Res = Unit;
+    }
+    impl<F, C: Index> TyMap<F, C> for Top {
+        type Res = Top;
+    }
+    impl<F, C: Index> TyMap<F, C> for Dyn {
+        type Res = Dyn;
+    }
+    impl<F, C: Index, T: Type, Tprime: Type, U: Type, Uprime: Type> TyMap<F, C> for Arrow<T, U>
+    where
+        T: TyMap<F, C, Res = Tprime>,
+        U: TyMap<F, C, Res = Uprime>,
+    {
+        type Res = Arrow<Tprime, Uprime>;
+    }
+    impl<F, C: Index, Tp: Type, Tpprime: Type> TyMap<F, C> for RefTy<Tp>
+    where
+        Tp: TyMap<F, C, Res = Tpprime>,
+    {
+        type Res = RefTy<Tpprime>;
+    }
+    impl<F, C: Index, Tp: Type, Tpprime: Type> TyMap<F, C> for Cont<Tp>
+    where
+        Tp: TyMap<F, C, Res = Tpprime>,
+    {
+        type Res = Cont<Tpprime>;
+    }
+    impl<F, C: Index, Tp: Type, Tpprime: Type> TyMap<F, C> for List<Tp>
+    where
+        Tp: TyMap<F, C, Res = Tpprime>,
+    {
+        type Res = List<Tpprime>;
+    }
+
+    trait FieldsMap<F, C: Index> {
+        type Res: FieldTys;
+    }
+    impl<F, C: Index> FieldsMap<F, C> for FieldTyNil {
+        type Res = FieldTyNil;
+    }
+    impl<F, C: Index, L: Label, Tp: Type, Tpprime: Type, Tl: FieldTys, Tlprime: FieldTys>
+        FieldsMap<F, C> for FieldTyCons<L, Tp, Tl>
+    where
+        Tp: TyMap<F, C, Res = Tpprime>,
+        Tl: FieldsMap<F, C, Res = Tlprime>,
+    {
+        type Res = FieldTyCons<L, Tpprime, Tlprime>;
+    }
+    impl<F, C: Index, Fs: FieldTys, Fsprime: FieldTys> TyMap<F, C> for RecordTy<Fs>
+    where
+        Fs: FieldsMap<F, C, Res = Fsprime>,
+    {
+        type Res = RecordTy<Fsprime>;
+    }
+    impl<F, C: Index, Cs: FieldTys, Csprime: FieldTys> TyMap<F, C> for VariantTy<Cs>
+    where
+        Cs: FieldsMap<F, C, Res = Csprime>,
+    {
+        type Res = VariantTy<Csprime>;
+    }
+
+    impl<F: MapTyVar<I, C>, C: Index, I: Index> TyMap<F, C> for TVar<I> {
+        type Res = F::Res;
+    }
+
+    impl<F, C: Index, Tp: Type, Tpprime: Type> TyMap<F, C> for Exists<Tp>
+    where
+        Tp: TyMap<F, ISucc<C>, Res = Tpprime>,
+    {
+        type Res = Exists<Tpprime>;
+    }
+}
+
+pub(crate) mod tyshiftby1 {
+    use super::*;
+    use tymap::{MapTyVar, TyMap};
+
+    pub trait TyShiftBy1 {
+        type Res: Type;
+    }
+    impl<Tp: Type, R: Type> TyShiftBy1 for Tp
+    where
+        Tp: TyMap<Up, I0, Res = R>,
+    {
+        type Res = R;
+    }
+
+    // Adds one to the type variables which are free, that is at least the cutoff
+    pub(crate) struct Up;
+    impl<I: Index, C: Index, R: Index> MapTyVar<I, C> for Up
+    where
+        I: ShiftVar<C, Res = R>,
+    {
+        type Res = TVar<R>;
+    }
+
+    trait ShiftVar<C: Index> {
+        type Res: Index;
+    }
+    impl<K: Index> ShiftVar<I0> for K {
+        type Res = ISucc<K>;
+    }
+    impl<N: Index> ShiftVar<ISucc<N>> for I0 {
+        type Res = I0;
+    }
+    impl<N: Index, K: Index, R: Index> ShiftVar<ISucc<N>> for ISucc<K>
+    where
+        K: ShiftVar<N, Res = R>,
+    {
+        type Res = ISucc<R>;
+    }
+}
+
+pub(crate) mod tysubst {
+    use super::*;
+    use tymap::{MapTyVar, TyMap};
+    use tyshiftby1::TyShiftBy1;
+
+    pub trait TySubst<J: Index, S: Type> {
+        type Res: Type;
+    }
+    impl<J: Index, S: Type, Tp: Type, R: Type> TySubst<J, S> for Tp
+    where
+        Tp: TyMap<Replace<J, S>, I0, Res = R>,
+    {
+        type Res = R;
+    }
+
+    // Replaces the type variable J by S
+    pub(crate) struct Replace<J: Index, S: Type>(PhantomData<(J, S)>);
+    impl<J: Index, S: Type, I: Index, R: Type> MapTyVar<I, I0> for Replace<J, S>
+    where
+        (I, J): Select<S, TVar<I>, Res = R>,
+    {
+        type Res = R;
+    }
+    // Under an Exists, J + 1 is replaced by S shifted once
+    impl<J: Index, S: Type, I: Index, C: Index, Sprime: Type, R: Type> MapTyVar<I, ISucc<C>>
+        for Replace<J, S>
+    where
+        S: TyShiftBy1<Res = Sprime>,
+        Replace<ISucc<J>, Sprime>: MapTyVar<I, C, Res = R>,
+    {
+        type Res = R;
+    }
+
+    // Selects S if both indices are equal, and K otherwise
+    trait Select<S: Type, K: Type> {
+        type Res: Type;
+    }
+    impl<S: Type, K: Type> Select<S, K> for (I0, I0) {
+        type Res = S;
+    }
+    impl<S: Type, K: Type, N: Index> Select<S, K> for (ISucc<N>, I0) {
+        type Res = K;
+    }
+    impl<S: Type, K: Type, N: Index> Select<S, K> for (I0, ISucc<N>) {
+        type Res = K;
+    }
+    impl<S: Type, K: Type, N1: Index, N2: Index, R: Type> Select<S, K> for (ISucc<N1>, ISucc<N2>)
+    where
+        (N1, N2): Select<S, K, Res = R>,
+    {
+        type Res = R;
+    }
+}
+
+pub(crate) mod tyshiftbyneg1 {
+    use super::*;
+    use tymap::{MapTyVar, TyMap};
+
+    pub trait TyShiftByNeg1 {
+        type Res: Type;
+    }
+    impl<Tp: Type, R: Type> TyShiftByNeg1 for Tp
+    where
+        Tp: TyMap<Down, I0, Res = R>,
+    {
+        type Res = R;
+    }
+
+    // Subtracts one from the type variables which are free, that is at least
+    // the cutoff. There is no rule for the cutoff itself, so shifting down
+    // fails if it occurs.
+    pub(crate) struct Down;
+    impl<I: Index, C: Index, R: Index> MapTyVar<I, C> for Down
+    where
+        I: ShiftVar<C, Res = R>,
+    {
+        type Res = TVar<R>;
+    }
+
+    trait ShiftVar<C: Index> {
+        type Res: Index;
+    }
+    impl<K: Index> ShiftVar<I0> for ISucc<K> {
+        type Res = K;
+    }
+    impl<N: Index> ShiftVar<ISucc<N>> for I0 {
+        type Res = I0;
+    }
+    impl<N: Index, K: Index, R: Index> ShiftVar<ISucc<N>> for ISucc<K>
+    where
+        K: ShiftVar<N, Res = R>,
+    {
+        type Res = ISucc<R>;
+    }
+}
+
+// Substitutes S for the type variable bound by an Exists in its body Tp
+trait TyBeta<S: Type> {
+    type Res: Type;
+}
+impl<Tp: Type, S: Type, Sprime: Type, Tpprime: Type, R: Type> TyBeta<S> for Tp
+where
+    S: TyShiftBy1<Res = Sprime>,
+    Tp: TySubst<I0, Sprime, Res = Tpprime>,
+    Tpprime: TyShiftByNeg1<Res = R>,
+{
+    type Res = R;
+}
+
+// Shifts the types of an environment when entering the scope of a new type variable
+trait TyShiftEnv: Env {
+    type Res: Env;
+}
+impl TyShiftEnv for EmptyEnv {
+    type Res = EmptyEnv;
+}
+impl<Tp: Type, Tpprime: Type, Tl: Env, Tlprime: Env> TyShiftEnv for TyCons<Tp, Tl>
+where
+    Tp: TyShiftBy1<Res = Tpprime>,
+    Tl: TyShiftEnv<Res = Tlprime>,
+{
+    type Res = TyCons<Tpprime, Tlprime>;
+}
+
+// T-Pack, the term must have the type of the body with the hidden type substituted
+impl<E: Env, St: Env, H: Type, T: Term, Tp: Type, Tpprime: Type> WellTyped<E, St>
+    for Pack<H, T, Exists<Tp>>
+where
+    Tp: TyBeta<H, Res = Tpprime>,
+    T: Checks<E, Tpprime, St>,
+{
+    type Tp = Exists<Tp>;
+}
+
+// T-Unpack, the body is typed with the type variable of the package in
+// scope. Its type is then shifted down, which fails if it mentions the
+// type variable, so that the hidden type cannot escape.
+impl<E: Env, St: Env, T: Term, Body: Term, Tp: Type, E1: Env, R: Type, Rprime: Type>
+    WellTyped<E, St> for Unpack<T, Body>
+where
+    T: WellTyped<E, St, Tp = Exists<Tp>>,
+    E: TyShiftEnv<Res = E1>,
+    Body: WellTyped<TyCons<Tp, E1>, St, Tp = R>,
+    R: TyShiftByNeg1<Res = Rprime>,
+{
+    type Tp = Rprime;
+}
+
+// Checking mode of the bidirectional type system, a term T implements
+// Checks<E, Tp, St> if it has type Tp under environment E (and store typing
+// St). Introduction forms push the expected type into their subterms, which
+// allows lambdas without parameter types (CLam), every other term simply
+// synthesizes its type.
+pub trait Checks<E: Env, Tp: Type, St: Env = EmptyEnv> {}
+
+// C-Lam
+impl<E: Env, St: Env, Tp1: Type, Tp2: Type, T: Term> Checks<E, Arrow<Tp1, Tp2>, St> for CLam<T> where
+    T: Checks<TyCons<Tp1, E>, Tp2, St>
+{
+}
+
+// C-TypedLam
+impl<E: Env, St: Env, Tp1: Type, Tp2: Type, T: Term> Checks<E, Arrow<Tp1, Tp2>, St> for Lam<Tp1, T> where
+    T: Checks<TyCons<Tp1, E>, Tp2, St>
+{
+}
+
+// C-If
+impl<E: Env, St: Env, Tp: Type, Cond: Term, Then: Term, Else: Term> Checks<E, Tp, St>
+    for If<Cond, Then, Else>
+where
+    Cond: WellTyped<E, St, Tp = Bool>,
+    Then: Checks<E, Tp, St>,
+    Else: Checks<E, Tp, St>,
+{
+}
+
+// C-Let
+impl<E: Env, St: Env, Tp: Type, Tp1: Type, T: Term, Body: Term> Checks<E, Tp, St> for Let<T, Body>
+where
+    T: WellTyped<E, St, Tp = Tp1>,
+    Body: Checks<TyCons<Tp1, E>, Tp, St>,
+{
+}
+
+// C-Fix
+impl<E: Env, St: Env, Tp: Type, T: Term> Checks<E, Tp, St> for Fix<T> where
+    T: Checks<E, Arrow<Tp, Tp>, St>
+{
+}
+
+// C-NatRec
+impl<E: Env, St: Env, Tp: Type, Z: Term, S: Term, N: Term> Checks<E, Tp, St> for NatRec<Z, S, N>
+where
+    Z: Checks<E, Tp, St>,
+    S: Checks<E, Arrow<Nat, Arrow<Tp, Tp>>, St>,
+    N: WellTyped<E, St, Tp = Nat>,
+{
+}
+
+// C-Raise
+impl<E: Env, St: Env, Tp: Type, T: Term> Checks<E, Tp, St> for Raise<T> where T: Checks<E, Nat, St> {}
+
+// C-Try
+impl<E: Env, St: Env, Tp: Type, T: Term, Handler: Term> Checks<E, Tp, St> for Try<T, Handler>
+where
+    T: Checks<E, Tp, St>,
+    Handler: Checks<TyCons<Nat, E>, Tp, St>,
+{
+}
+
+// C-CallCC
+impl<E: Env, St: Env, Tp: Type, T: Term> Checks<E, Tp, St> for CallCC<T> where
+    T: Checks<E, Arrow<Cont<Tp>, Tp>, St>
+{
+}
+
+// C-Throw, like raise it can only be checked
+impl<E: Env, St: Env, Tp: Type, Tp1: Type, K: Term, V: Term> Checks<E, Tp, St> for Throw<K, V>
+where
+    K: WellTyped<E, St, Tp = Cont<Tp1>>,
+    V: Checks<E, Tp1, St>,
+{
+}
+
+// C-Record, the type of each field is pushed into it
+trait FieldsCheck<E: Env, Tys: FieldTys, St: Env> {}
+impl<E: Env, St: Env> FieldsCheck<E, FieldTyNil, St> for FieldNil {}
+impl<E: Env, St: Env, L: Label, T: Term, Tl: FieldList, Tp: Type, TlTys: FieldTys>
+    FieldsCheck<E, FieldTyCons<L, Tp, TlTys>, St> for FieldCons<L, T, Tl>
+where
+    T: Checks<E, Tp, St>,
+    Tl: FieldsCheck<E, TlTys, St>,
+{
+}
+impl<E: Env, St: Env, Fs: FieldList, Tys: FieldTys> Checks<E, RecordTy<Tys>, St> for Record<Fs> where
+    Fs: FieldsCheck<E, Tys, St>
+{
+}
+
+// C-Match
+impl<E: Env, St: Env, Tp: Type, T: Term, Bs: BranchList, Cs: FieldTys> Checks<E, Tp, St>
+    for Match<T, Bs>
+where
+    T: WellTyped<E, St, Tp = VariantTy<Cs>>,
+    Cs: BranchesCheck<E, St, Bs, Tp>,
+    Bs: SameLength<Cs>,
+{
+}
+
+// C-Nil
+impl<E: Env, St: Env, Tp: Type> Checks<E, List<Tp>, St> for CNil {}
+
+// C-Cons
+impl<E: Env, St: Env, Tp: Type, H: Term, T: Term> Checks<E, List<Tp>, St> for Cons<H, T>
+where
+    H: Checks<E, Tp, St>,
+    T: Checks<E, List<Tp>, St>,
+{
+}
+
+// C-ListCase
+impl<E: Env, St: Env, Tp: Type, ETp: Type, L: Term, NilBranch: Term, ConsBranch: Term>
+    Checks<E, Tp, St> for ListCase<L, NilBranch, ConsBranch>
+where
+    L: WellTyped<E, St, Tp = List<ETp>>,
+    NilBranch: Checks<E, Tp, St>,
+    ConsBranch: Checks<TyCons<List<ETp>, TyCons<ETp, E>>, Tp, St>,
+{
+}
+
+// C-Unpack, the expected type is shifted into the scope of the type variable
+impl<E: Env, St: Env, Tp: Type, T: Term, Body: Term, XTp: Type, E1: Env, Tpprime: Type>
+    Checks<E, Tp, St> for Unpack<T, Body>
+where
+    T: WellTyped<E, St, Tp = Exists<XTp>>,
+    E: TyShiftEnv<Res = E1>,
+    Tp: TyShiftBy1<Res = Tpprime>,
+    Body: Checks<TyCons<XTp, E1>, Tpprime, St>,
+{
+}
+
+// C-Synth, for all the remaining terms
+impl<E: Env, St: Env> Checks<E, Bool, St> for True {}
+impl<E: Env, St: Env> Checks<E, Bool, St> for False {}
+impl<E: Env, St: Env> Checks<E, Nat, St> for Zero {}
+impl<E: Env, St: Env> Checks<E, Unit, St> for Triv {}
+impl<E: Env, St: Env, T: Term> Checks<E, Nat, St> for Succ<T> where Succ<T>: WellTyped<E, St> {}
+impl<E: Env, St: Env, T: Term> Checks<E, Bool, St> for IsZero<T> where IsZero<T>: WellTyped<E, St> {}
+impl<E: Env, St: Env, T: Term> Checks<E, Nat, St> for Pred<T> where Pred<T>: WellTyped<E, St> {}
+impl<E: Env, St: Env, Tp: Type, I: Index> Checks<E, Tp, St> for Var<I> where
+    Var<I>: WellTyped<E, St, Tp = Tp>
+{
+}
+impl<E: Env, St: Env, Tp: Type, F: Term, T: Term> Checks<E, Tp, St> for App<F, T> where
+    App<F, T>: WellTyped<E, St, Tp = Tp>
+{
+}
+impl<E: Env, St: Env, Tp: Type, T: Term> Checks<E, Tp, St> for Ann<T, Tp> where T: Checks<E, Tp, St> {}
+impl<E: Env, St: Env, Tp: Type, Fs: FnList, K: Index> Checks<E, Tp, St> for FixN<Fs, K> where
+    FixN<Fs, K>: WellTyped<E, St, Tp = Tp>
+{
+}
+
+impl<E: Env, St: Env, Tp: Type, T: Term> Checks<E, Tp, St> for Ref<T> where
+    Ref<T>: WellTyped<E, St, Tp = Tp>
+{
+}
+impl<E: Env, St: Env, Tp: Type, T: Term> Checks<E, Tp, St> for Deref<T> where
+    Deref<T>: WellTyped<E, St, Tp = Tp>
+{
+}
+impl<E: Env, St: Env, L: Term, T: Term> Checks<E, Unit, St> for Assign<L, T> where
+    Assign<L, T>: WellTyped<E, St>
+{
+}
+impl<E: Env, St: Env, Tp: Type, I: Index> Checks<E, Tp, St> for Loc<I> where
+    Loc<I>: WellTyped<E, St, Tp = Tp>
+{
+}
+
+impl<E: Env, St: Env, T: Term> Checks<E, Unit, St> for Print<T> where Print<T>: WellTyped<E, St> {}
+impl<E: Env, St: Env, Tp: Type, T: Term, L: Label> Checks<E, Tp, St> for Proj<T, L> where
+    Proj<T, L>: WellTyped<E, St, Tp = Tp>
+{
+}
+impl<E: Env, St: Env, Tp: Type> Checks<E, List<Tp>, St> for Nil<Tp> {}
+impl<E: Env, St: Env, Tp: Type, H: Type, T: Term, ExTp: Type> Checks<E, Tp, St> for Pack<H, T, ExTp> where
+    Pack<H, T, ExTp>: WellTyped<E, St, Tp = Tp>
+{
+}
+impl<E: Env, St: Env, Tp: Type, L: Label, T: Term, VTp: Type> Checks<E, Tp, St> for Tag<L, T, VTp> where
+    Tag<L, T, VTp>: WellTyped<E, St, Tp = Tp>
+{
+}
+
+// A term is Total if it does not use general recursion, that is if it
+// contains no Fix. Well-typed total terms always terminate. References are
+// excluded as well, since recursion can be encoded by storing a function.
+pub trait Total: Term {}
+impl Total for True {}
+impl Total for False {}
+impl Total for Zero {}
+impl Total for Triv {}
+impl<T: Total> Total for Succ<T> {}
+impl<T: Total> Total for IsZero<T> {}
+impl<T: Total> Total for Pred<T> {}
+impl<I: Index> Total for Var<I> {}
+impl<Tp: Type, T: Total> Total for Lam<Tp, T> {}
+impl<T: Total> Total for CLam<T> {}
+impl<F: Total, T: Total> Total for App<F, T> {}
+impl<C: Total, T: Total, E: Total> Total for If<C, T, E> {}
+impl<T: Total, Body: Total> Total for Let<T, Body> {}
+impl<T: Total, Tp: Type> Total for Ann<T, Tp> {}
+impl<Z: Total, S: Total, N: Total> Total for NatRec<Z, S, N> {}
+impl<T: Total> Total for Raise<T> {}
+impl<T: Total, Handler: Total> Total for Try<T, Handler> {}
+impl<T: Total> Total for Print<T> {}
+impl<T: Total> Total for CallCC<T> {}
+impl<K: Total, V: Total> Total for Throw<K, V> {}
+impl<E: Env, T: Total> Total for Open<E, T> {}
+impl<Fs: FieldList + TotalFields> Total for Record<Fs> {}
+impl<T: Total, L: Label> Total for Proj<T, L> {}
+impl<L: Label, T: Total, Tp: Type> Total for Tag<L, T, Tp> {}
+impl<T: Total, Bs: BranchList + TotalBranches> Total for Match<T, Bs> {}
+impl<Tp: Type> Total for Nil<Tp> {}
+impl Total for CNil {}
+impl<H: Total, T: Total> Total for Cons<H, T> {}
+impl<L: Total, N: Total, C: Total> Total for ListCase<L, N, C> {}
+impl<H: Type, T: Total, ExTp: Type> Total for Pack<H, T, ExTp> {}
+impl<T: Total, Body: Total> Total for Unpack<T, Body> {}
+
+trait TotalFields {}
+impl TotalFields for FieldNil {}
+impl<L: Label, T: Total, Tl: FieldList + TotalFields> TotalFields for FieldCons<L, T, Tl> {}
+
+trait TotalBranches {}
+impl TotalBranches for BranchNil {}
+impl<L: Label, T: Total, Tl: BranchList + TotalBranches> TotalBranches for BranchCons<L, T, Tl> {}
+
+/// The term T itself, which must be closed and well typed. Terms spliced into
+/// stlc! programs go through it, so that a term with free variables is
+/// rejected rather than captured by the binders around the splice. An open
+/// program is rejected as well, even though it carries the types of its free
+/// variables, since it is only typed in their environment:
+///
+/// ```compile_fail
+/// use stlcrts::*;
+/// use stlcrts_macros::stlc;
+///
+/// type OpenProg = stlc! { with x: Nat; succ x };
+/// type_checks::<stlc! { let y = $OpenProg in iszero y }>();
+/// ```
+pub trait Closed {
+    type Res: Term;
+}
+impl<T: Term> Closed for T
+where
+    T: WellTyped<EmptyEnv>,
+{
+    type Res = T;
+}
+
+/// The constant stlc_defs! declares along with each definition, under the
+/// same name. A name no binder of a program binds refers to a definition,
+/// and is only accepted when this constant is found for it, so that another
+/// type of that name, such as a label of names!, is not spliced instead.
+pub struct DefMarker<T: Term>(pub PhantomData<T>);
+
+/// The definition T of a marker, the length of the array being where the
+/// marker is checked
+pub trait Definition {
+    type Res: Term;
+}
+impl<T: Term> Definition for [DefMarker<T>; 0] {
+    type Res = T;
+}
+
 /// Util function to type-check a given term (given as a generic argument)
 pub fn type_checks<T>()
 where
@@ -109,3 +1065,34 @@ where
     T: Term,
 {
 }
+
+/// Util function to check that a closed term has the type Tp
+pub fn type_checks_as<T, Tp>()
+where
+    Tp: Type,
+    T: WellTyped<EmptyEnv, Tp = Tp>,
+    T: Term,
+{
+}
+
+/// Same as `type_checks` but under a given environment E for the free variables of T
+pub fn type_checks_in<E, T>()
+where
+    E: Env,
+    T: WellTyped<E>,
+    T: Term,
+{
+}
+
+/// Util function to compute the type of T under environment E
+pub fn type_of_in<E, T>() -> PhantomData<<T as WellTyped<E>>::Tp>
+where
+    E: Env,
+    T: WellTyped<E>,
+    T: Term,
+{
+    PhantomData
+}
+
+/// Util function to check that a term does not use general recursion
+pub fn is_total<T: Total>() {}
@@ -0,0 +1,318 @@
+use crate::{evaluator::Eval, term::*, typechecker::WellTyped};
+use std::marker::PhantomData;
+
+// Type erasure, from the typed terms to the untyped lambda calculus with
+// booleans, natural numbers, exceptions, records, variants, lists,
+// references, printing and continuations. Every abstraction becomes a ULam, annotations, packages and
+// casts are dropped, nil loses its type and tags and the functions of a
+// recursive group are given the type Top. The other terms carry no types.
+pub trait Erase {
+    type Res: Term;
+}
+
+impl Erase for True {
+    type Res = True;
+}
+impl Erase for False {
+    type Res = False;
+}
+impl Erase for Zero {
+    type Res = Zero;
+}
+impl Erase for Triv {
+    type Res = Triv;
+}
+impl<I: Index> Erase for Var<I> {
+    type Res = Var<I>;
+}
+
+impl<Tp: Type, T: Term, R: Term> Erase for Lam<Tp, T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = ULam<R>;
+}
+impl<T: Term, R: Term> Erase for CLam<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = ULam<R>;
+}
+impl<T: Term, R: Term> Erase for ULam<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = ULam<R>;
+}
+
+impl<T: Term, Tp: Type, R: Term> Erase for Ann<T, Tp>
+where
+    T: Erase<Res = R>,
+{
+    type Res = R;
+}
+
+impl<T1: Term, T2: Term, R1: Term, R2: Term> Erase for App<T1, T2>
+where
+    T1: Erase<Res = R1>,
+    T2: Erase<Res = R2>,
+{
+    type Res = App<R1, R2>;
+}
+
+impl<T1: Term, T2: Term, R1: Term, R2: Term> Erase for Let<T1, T2>
+where
+    T1: Erase<Res = R1>,
+    T2: Erase<Res = R2>,
+{
+    type Res = Let<R1, R2>;
+}
+
+impl<C: Term, T1: Term, T2: Term, Cprime: Term, R1: Term, R2: Term> Erase for If<C, T1, T2>
+where
+    C: Erase<Res = Cprime>,
+    T1: Erase<Res = R1>,
+    T2: Erase<Res = R2>,
+{
+    type Res = If<Cprime, R1, R2>;
+}
+
+impl<T: Term, R: Term> Erase for Succ<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Succ<R>;
+}
+impl<T: Term, R: Term> Erase for Pred<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Pred<R>;
+}
+impl<T: Term, R: Term> Erase for IsZero<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = IsZero<R>;
+}
+
+impl<T: Term, R: Term> Erase for Fix<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Fix<R>;
+}
+
+impl<Fs: FnList, K: Index, R: FnList> Erase for FixN<Fs, K>
+where
+    Fs: EraseFns<Res = R>,
+{
+    type Res = FixN<R, K>;
+}
+trait EraseFns: FnList {
+    type Res: FnList;
+}
+impl EraseFns for FnNil {
+    type Res = FnNil;
+}
+impl<Tp: Type, T: Term, Tl: FnList, R: Term, Rs: FnList> EraseFns for FnCons<Tp, T, Tl>
+where
+    T: Erase<Res = R>,
+    Tl: EraseFns<Res = Rs>,
+{
+    type Res = FnCons<Top, R, Rs>;
+}
+
+impl<Z: Term, S: Term, N: Term, Zprime: Term, Sprime: Term, Nprime: Term> Erase for NatRec<Z, S, N>
+where
+    Z: Erase<Res = Zprime>,
+    S: Erase<Res = Sprime>,
+    N: Erase<Res = Nprime>,
+{
+    type Res = NatRec<Zprime, Sprime, Nprime>;
+}
+
+impl<T: Term, R: Term> Erase for Raise<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Raise<R>;
+}
+impl<T: Term, H: Term, R: Term, Hprime: Term> Erase for Try<T, H>
+where
+    T: Erase<Res = R>,
+    H: Erase<Res = Hprime>,
+{
+    type Res = Try<R, Hprime>;
+}
+
+impl<Fs: FieldList, R: FieldList> Erase for Record<Fs>
+where
+    Fs: EraseFields<Res = R>,
+{
+    type Res = Record<R>;
+}
+trait EraseFields: FieldList {
+    type Res: FieldList;
+}
+impl EraseFields for FieldNil {
+    type Res = FieldNil;
+}
+impl<L: Label, T: Term, Tl: FieldList, R: Term, Rs: FieldList> EraseFields for FieldCons<L, T, Tl>
+where
+    T: Erase<Res = R>,
+    Tl: EraseFields<Res = Rs>,
+{
+    type Res = FieldCons<L, R, Rs>;
+}
+impl<T: Term, L: Label, R: Term> Erase for Proj<T, L>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Proj<R, L>;
+}
+
+impl<L: Label, T: Term, Tp: Type, R: Term> Erase for Tag<L, T, Tp>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Tag<L, R, Top>;
+}
+impl<T: Term, Bs: BranchList, R: Term, Rs: BranchList> Erase for Match<T, Bs>
+where
+    T: Erase<Res = R>,
+    Bs: EraseBranches<Res = Rs>,
+{
+    type Res = Match<R, Rs>;
+}
+trait EraseBranches: BranchList {
+    type Res: BranchList;
+}
+impl EraseBranches for BranchNil {
+    type Res = BranchNil;
+}
+impl<L: Label, T: Term, Tl: BranchList, R: Term, Rs: BranchList> EraseBranches
+    for BranchCons<L, T, Tl>
+where
+    T: Erase<Res = R>,
+    Tl: EraseBranches<Res = Rs>,
+{
+    type Res = BranchCons<L, R, Rs>;
+}
+
+impl<Tp: Type> Erase for Nil<Tp> {
+    type Res = CNil;
+}
+impl Erase for CNil {
+    type Res = CNil;
+}
+impl<H: Term, T: Term, Hprime: Term, R: Term> Erase for Cons<H, T>
+where
+    H: Erase<Res = Hprime>,
+    T: Erase<Res = R>,
+{
+    type Res = Cons<Hprime, R>;
+}
+impl<L: Term, N: Term, B: Term, Lprime: Term, Nprime: Term, Bprime: Term> Erase
+    for ListCase<L, N, B>
+where
+    L: Erase<Res = Lprime>,
+    N: Erase<Res = Nprime>,
+    B: Erase<Res = Bprime>,
+{
+    type Res = ListCase<Lprime, Nprime, Bprime>;
+}
+
+// A package is its term, unpacking it binds the term like a let
+impl<H: Type, T: Term, ExTp: Type, R: Term> Erase for Pack<H, T, ExTp>
+where
+    T: Erase<Res = R>,
+{
+    type Res = R;
+}
+impl<T: Term, B: Term, R: Term, Bprime: Term> Erase for Unpack<T, B>
+where
+    T: Erase<Res = R>,
+    B: Erase<Res = Bprime>,
+{
+    type Res = Let<R, Bprime>;
+}
+
+impl<I: Index> Erase for Loc<I> {
+    type Res = Loc<I>;
+}
+impl<T: Term, R: Term> Erase for Ref<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Ref<R>;
+}
+impl<T: Term, R: Term> Erase for Deref<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Deref<R>;
+}
+impl<L: Term, T: Term, Lprime: Term, R: Term> Erase for Assign<L, T>
+where
+    L: Erase<Res = Lprime>,
+    T: Erase<Res = R>,
+{
+    type Res = Assign<Lprime, R>;
+}
+
+impl<T: Term, R: Term> Erase for Print<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = Print<R>;
+}
+
+impl<T: Term, R: Term> Erase for CallCC<T>
+where
+    T: Erase<Res = R>,
+{
+    type Res = CallCC<R>;
+}
+impl<K: Term, V: Term, Kprime: Term, R: Term> Erase for Throw<K, V>
+where
+    K: Erase<Res = Kprime>,
+    V: Erase<Res = R>,
+{
+    type Res = Throw<Kprime, R>;
+}
+
+// Without types there is nothing left to check, a cast is its term. The
+// erasure of a program which is blamed may then run further or get stuck.
+impl<T: Term, From: Type, To: Type, R: Term> Erase for Cast<T, From, To>
+where
+    T: Erase<Res = R>,
+{
+    type Res = R;
+}
+impl Erase for Blame {
+    type Res = Blame;
+}
+
+/// Util function to evaluate the erasure of T
+pub fn erase_eval<T>() -> PhantomData<<<T as Erase>::Res as Eval>::Res>
+where
+    T: Term,
+    T: Erase,
+    <T as Erase>::Res: Eval,
+{
+    PhantomData
+}
+
+/// Util function to check that erasing a closed well-typed term and then
+/// evaluating it gives the erasure of its value
+pub fn erasure_agrees<T>()
+where
+    T: Term,
+    T: WellTyped<EmptyEnv>,
+    T: Eval,
+    T: Erase,
+    <T as Eval>::Res: Erase,
+    <T as Erase>::Res: Eval<Res = <<T as Eval>::Res as Erase>::Res>,
+{
+}
@@ -0,0 +1,215 @@
+//! Compile-time benchmarks for the type-level evaluator.
+//!
+//! Since type-checking and evaluating a `stlc!` program is done by rustc,
+//! the cost of a change to the type checker or the evaluator is compile time.
+//! This generates a crate with one binary per program of each family and
+//! size, builds each of them and reports the wall-clock time it took.
+//!
+//! Usage: `cargo run -p stlcrts_bench [--opt] [--three-pass] [family [size...]]`,
+//! where `--opt` builds the programs with the `#![opt]` flag of `stlc!` and
+//! `--three-pass` builds stlcrts with its `three-pass` feature, which beta
+//! reduces by shifting, substituting and shifting back
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// A parametric family of programs, and the sizes it is run at by default
+struct Family {
+    name: &'static str,
+    sizes: &'static [usize],
+    program: fn(usize) -> String,
+}
+
+const FAMILIES: &[Family] = &[
+    Family {
+        name: "fact",
+        sizes: &[1, 2, 3, 4],
+        program: fact,
+    },
+    Family {
+        name: "church",
+        sizes: &[8, 16, 32, 64],
+        program: church,
+    },
+    Family {
+        name: "let",
+        sizes: &[8, 16, 24, 32],
+        program: let_chain,
+    },
+    Family {
+        name: "app",
+        sizes: &[8, 16, 32, 64],
+        program: wide_app,
+    },
+];
+
+/// `fact n`, with multiplication and addition defined by recursion
+fn fact(n: usize) -> String {
+    format!(
+        "let rec add (a: Nat) (b: Nat) : Nat = if iszero a then b else succ (add (pred a) b) in
+         let rec mul (a: Nat) (b: Nat) : Nat = if iszero a then 0 else add b (mul (pred a) b) in
+         let rec fact (a: Nat) : Nat = if iszero a then 1 else mul a (fact (pred a)) in
+         fact {n}"
+    )
+}
+
+/// The Church numeral n, applied to the successor and zero
+fn church(n: usize) -> String {
+    let body = (0..n).fold("x".to_string(), |acc, _| format!("f ({acc})"));
+    format!(
+        "let c = fn f: (Nat -> Nat) => fn x: Nat => {body} in
+         c (fn k: Nat => succ k) 0"
+    )
+}
+
+/// n nested lets, each one using the previous one
+fn let_chain(n: usize) -> String {
+    let lets: String = (1..=n)
+        .map(|i| format!("let x{i} = succ x{} in ", i - 1))
+        .collect();
+    format!("let x0 = 0 in {lets}x{n}")
+}
+
+/// A function of n parameters applied to n arguments
+fn wide_app(n: usize) -> String {
+    let params: String = (0..n).map(|i| format!("fn a{i}: Nat => ")).collect();
+    let args = vec!["0"; n].join(" ");
+    format!("({params}a0) {args}")
+}
+
+fn bin_source(program: &str, opt: bool) -> String {
+    let flags = if opt { "#![opt] " } else { "" };
+    format!(
+        "#![recursion_limit = \"4096\"]
+
+use stlcrts::*;
+use stlcrts_macros::stlc;
+
+stlc! {{ {flags}type P = {program} }}
+
+fn main() {{
+    type_checks::<P>();
+    let _ = eval::<P>();
+}}
+"
+    )
+}
+
+/// Writes the manifest of the generated crate, which is a workspace of its own
+fn setup(dir: &Path, three_pass: bool) -> std::io::Result<()> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let root = root.canonicalize()?;
+    let bins = dir.join("src").join("bin");
+    if bins.exists() {
+        fs::remove_dir_all(&bins)?;
+    }
+    fs::create_dir_all(&bins)?;
+    fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]
+name = \"stlcrts_bench_programs\"
+version = \"0.1.0\"
+edition = \"2024\"
+
+[dependencies]
+stlcrts = {{ path = {:?}, features = {:?} }}
+stlcrts_macros = {{ path = {:?} }}
+
+[workspace]
+",
+            root.join("stlcrts"),
+            if three_pass { &["three-pass"][..] } else { &[] },
+            root.join("stlcrts_macros"),
+        ),
+    )?;
+    fs::write(bins.join("warmup.rs"), bin_source("0", false))
+}
+
+/// Builds one binary of the generated crate, returning how long it took
+/// or None if it did not compile
+fn build(dir: &Path, bin: &str) -> Option<Duration> {
+    let start = Instant::now();
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--quiet", "--bin", bin])
+        .current_dir(dir)
+        .env("CARGO_INCREMENTAL", "0")
+        .stderr(Stdio::null())
+        .status()
+        .expect("failed to run cargo");
+    status.success().then(|| start.elapsed())
+}
+
+fn main() -> std::io::Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut flag = |name: &str| match args.iter().position(|arg| arg == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    let opt = flag("--opt");
+    let three_pass = flag("--three-pass");
+    let runs: Vec<(&Family, Vec<usize>)> = match args.split_first() {
+        None => FAMILIES.iter().map(|f| (f, f.sizes.to_vec())).collect(),
+        Some((name, sizes)) => {
+            let Some(family) = FAMILIES.iter().find(|f| f.name == name) else {
+                let names: Vec<_> = FAMILIES.iter().map(|f| f.name).collect();
+                eprintln!(
+                    "unknown family {name}, expected one of {}",
+                    names.join(", ")
+                );
+                std::process::exit(2);
+            };
+            let sizes = if sizes.is_empty() {
+                family.sizes.to_vec()
+            } else {
+                sizes
+                    .iter()
+                    .map(|s| s.parse().expect("sizes are natural numbers"))
+                    .collect()
+            };
+            vec![(family, sizes)]
+        }
+    };
+
+    let dir: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("target")
+        .join("bench");
+    setup(&dir, three_pass)?;
+    for (family, sizes) in &runs {
+        for size in sizes {
+            let source = bin_source(&(family.program)(*size), opt);
+            fs::write(
+                dir.join("src")
+                    .join("bin")
+                    .join(format!("{}_{size}.rs", family.name)),
+                source,
+            )?;
+        }
+    }
+
+    // The dependencies are built once, before any measurement
+    if build(&dir, "warmup").is_none() {
+        eprintln!("failed to build the benchmark crate in {}", dir.display());
+        std::process::exit(1);
+    }
+
+    println!("{:<8} {:>6} {:>10}", "family", "size", "time (s)");
+    for (family, sizes) in &runs {
+        for size in sizes {
+            let time = match build(&dir, &format!("{}_{size}", family.name)) {
+                Some(time) => format!("{:.2}", time.as_secs_f64()),
+                None => "failed".to_string(),
+            };
+            println!("{:<8} {:>6} {:>10}", family.name, size, time);
+        }
+    }
+    Ok(())
+}
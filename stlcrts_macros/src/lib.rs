@@ -1,13 +1,30 @@
+use std::collections::HashMap;
+
+mod native;
+mod opt;
+mod prelude;
 use syn::{
     Ident, LitBool, LitInt, Result, Token,
     parse::{Parse, ParseStream},
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum Tp {
     Bool,
     Nat,
+    Unit,
+    Top,
+    Dyn,
+    Ref(Box<Tp>),
+    Cont(Box<Tp>),
+    List(Box<Tp>),
+    Record(Vec<(String, Tp)>),
+    Variant(Vec<(String, Tp)>),
     Arrow(Box<Tp>, Box<Tp>),
+    /// Type variable, by name as written and by index once resolved
+    Var(String),
+    TVar(usize),
+    Exists(String, Box<Tp>),
 }
 
 impl Parse for Tp {
@@ -18,35 +35,118 @@ impl Parse for Tp {
         } else if input.peek(kw::Nat) {
             input.parse::<kw::Nat>()?;
             Ok(Tp::Nat)
+        } else if input.peek(kw::Unit) {
+            input.parse::<kw::Unit>()?;
+            Ok(Tp::Unit)
+        } else if input.peek(kw::Top) {
+            input.parse::<kw::Top>()?;
+            Ok(Tp::Top)
+        } else if input.peek(kw::Dyn) {
+            input.parse::<kw::Dyn>()?;
+            Ok(Tp::Dyn)
+        } else if input.peek(kw::Ref) {
+            input.parse::<kw::Ref>()?;
+            let tp = input.parse()?;
+            Ok(Tp::Ref(Box::new(tp)))
+        } else if input.peek(kw::Cont) {
+            input.parse::<kw::Cont>()?;
+            let tp = input.parse()?;
+            Ok(Tp::Cont(Box::new(tp)))
+        } else if input.peek(kw::exists) {
+            input.parse::<kw::exists>()?;
+            let name: Ident = input.parse()?;
+            input.parse::<Token![.]>()?;
+            let tp = input.parse()?;
+            Ok(Tp::Exists(name.to_string(), Box::new(tp)))
+        } else if input.peek(kw::List) {
+            input.parse::<kw::List>()?;
+            let tp = input.parse()?;
+            Ok(Tp::List(Box::new(tp)))
+        } else if input.peek(syn::token::Brace) {
+            let content;
+            syn::braced!(content in input);
+            let fields = parse_fields(&content, |content| {
+                content.parse::<Token![:]>()?;
+                content.parse()
+            })?;
+            Ok(Tp::Record(fields))
+        } else if input.peek(Token![<]) {
+            input.parse::<Token![<]>()?;
+            let mut cases: Vec<(String, Tp)> = Vec::new();
+            while !input.peek(Token![>]) {
+                let ident: Ident = input.parse()?;
+                let name = ident.to_string();
+                if cases.iter().any(|(n, _)| *n == name) {
+                    return Err(syn::Error::new(ident.span(), "duplicate case"));
+                }
+                input.parse::<Token![:]>()?;
+                cases.push((name, input.parse()?));
+                if !input.peek(Token![>]) {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            input.parse::<Token![>]>()?;
+            Ok(Tp::Variant(cases))
         } else if input.peek(syn::token::Paren) {
             let content;
             syn::parenthesized!(content in input);
             let left = content.parse()?;
+            if content.is_empty() {
+                return Ok(left);
+            }
             content.parse::<Token![->]>()?;
             let right = content.parse()?;
             Ok(Tp::Arrow(Box::new(left), Box::new(right)))
+        } else if input.peek(Ident) {
+            let name: Ident = input.parse()?;
+            Ok(Tp::Var(name.to_string()))
         } else {
             Err(syn::Error::new(
                 input.span(),
-                "expected type (Bool, Nat, or (Tp -> Tp))",
+                "expected type (Bool, Nat, Unit, Top, Dyn, Ref Tp, Cont Tp, List Tp, {x: Tp, ...}, <x: Tp, ...>, exists X. Tp, X or (Tp -> Tp))",
             ))
         }
     }
 }
 
+/// Comma separated fields `x <sep> e` of a record or a record type,
+/// the part after the name is parsed by `field`
+fn parse_fields<T>(
+    input: ParseStream,
+    field: impl Fn(ParseStream) -> Result<T>,
+) -> Result<Vec<(String, T)>> {
+    let mut fields: Vec<(String, T)> = Vec::new();
+    while !input.is_empty() {
+        let ident: Ident = input.parse()?;
+        let name = ident.to_string();
+        if fields.iter().any(|(n, _)| *n == name) {
+            return Err(syn::Error::new(ident.span(), "duplicate field"));
+        }
+        let value = field(input)?;
+        fields.push((name, value));
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    Ok(fields)
+}
+
 /// AST Obtained from the macro, which uses string as names rather than indices
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum Expr {
+    Unit,
     Bool(bool),
     Nat(u64),
     Var(String),
     If(Box<Expr>, Box<Expr>, Box<Expr>),
     Lam {
         param: String,
-        tp: Tp,
+        tp: Option<Tp>,
         body: Box<Expr>,
     },
     App(Box<Expr>, Box<Expr>),
+    Ann(Box<Expr>, Tp),
     Let {
         name: String,
         value: Box<Expr>,
@@ -60,10 +160,79 @@ enum Expr {
         scope: Box<Expr>,
         is_rec: bool,
     },
+    /// Group of mutually recursive functions, `let rec f .. and g .. in scope`
+    LetRecGroup {
+        fns: Vec<RecFn>,
+        scope: Box<Expr>,
+    },
     Fix(Box<Expr>),
+    NatRec(Box<Expr>, Box<Expr>, Box<Expr>),
+    Raise(Box<Expr>),
+    Ref(Box<Expr>),
+    Deref(Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    Print(Box<Expr>),
+    CallCC(Box<Expr>),
+    Throw(Box<Expr>, Box<Expr>),
+    Record(Vec<(String, Expr)>),
+    Proj(Box<Expr>, String),
+    /// Injection `<l = e> as Tp` into a variant type
+    Tag {
+        label: String,
+        value: Box<Expr>,
+        tp: Tp,
+    },
+    /// `case e of <l = x> => e1 | ...`, each branch has a label and binds a name
+    Match {
+        scrutinee: Box<Expr>,
+        branches: Vec<(String, String, Expr)>,
+    },
+    /// `pack Tp, e as exists X. Tp`
+    Pack {
+        hidden: Tp,
+        value: Box<Expr>,
+        tp: Tp,
+    },
+    /// `let {X, x} = e in body`
+    Unpack {
+        tyvar: String,
+        name: String,
+        value: Box<Expr>,
+        body: Box<Expr>,
+    },
+    /// List literal `[e1, e2, ...]`
+    List(Vec<Expr>),
+    Cons(Box<Expr>, Box<Expr>),
+    /// `case e of [] => e1 | h :: t => e2`
+    ListCase {
+        scrutinee: Box<Expr>,
+        nil: Box<Expr>,
+        head: String,
+        tail: String,
+        cons: Box<Expr>,
+    },
+    /// Sequence `e1; e2`, the value of e1 is discarded
+    Seq(Box<Expr>, Box<Expr>),
+    Try {
+        body: Box<Expr>,
+        name: String,
+        handler: Box<Expr>,
+    },
     IsZero(Box<Expr>),
     Succ(Box<Expr>),
     Pred(Box<Expr>),
+    /// A Rust type spliced into the program, `$Name` or `#{Type}`
+    Splice(syn::Type),
+    /// A definition of the prelude given by its path and its term
+    Def(syn::Path, Box<DBExpr>),
+}
+
+#[derive(Clone, Debug)]
+struct RecFn {
+    name: String,
+    params: Vec<(String, Tp)>,
+    return_type: Tp,
+    body: Expr,
 }
 
 mod kw {
@@ -71,15 +240,116 @@ mod kw {
     syn::custom_keyword!(fix);
     syn::custom_keyword!(Bool);
     syn::custom_keyword!(Nat);
+    syn::custom_keyword!(Unit);
+    syn::custom_keyword!(Ref);
     syn::custom_keyword!(iszero);
     syn::custom_keyword!(succ);
     syn::custom_keyword!(pred);
     syn::custom_keyword!(rec);
+    syn::custom_keyword!(with);
+    syn::custom_keyword!(and);
+    syn::custom_keyword!(natrec);
+    syn::custom_keyword!(raise);
+    syn::custom_keyword!(print);
+    syn::custom_keyword!(Cont);
+    syn::custom_keyword!(callcc);
+    syn::custom_keyword!(throw);
+    syn::custom_keyword!(case);
+    syn::custom_keyword!(of);
+    syn::custom_keyword!(List);
+    syn::custom_keyword!(Top);
+    syn::custom_keyword!(Dyn);
+    syn::custom_keyword!(exists);
+    syn::custom_keyword!(pack);
+    syn::custom_keyword!(def);
+    syn::custom_keyword!(prelude);
+}
+
+/// Full input of the macro, an optional `use prelude;` header, an optional
+/// `with x: Tp, ...;` header declaring the free variables of the program,
+/// followed by the program. Whether it is optimized is given by the
+/// `#![opt]` flag of the macro.
+struct Program {
+    opt: bool,
+    prelude: bool,
+    free: Vec<(String, Tp)>,
+    expr: Expr,
+}
+
+/// Flags given as inner attributes at the start of the input of a macro,
+/// `#![opt]`, each of them one of allowed
+fn parse_flags(input: ParseStream, allowed: &[&str]) -> Result<Vec<String>> {
+    let mut flags = Vec::new();
+    while input.peek(Token![#]) && input.peek2(Token![!]) {
+        input.parse::<Token![#]>()?;
+        input.parse::<Token![!]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        let flag: Ident = content.parse()?;
+        if !allowed.iter().any(|name| flag == name) {
+            return Err(syn::Error::new(
+                flag.span(),
+                format!("unknown flag, expected {}", allowed.join(" or ")),
+            ));
+        }
+        flags.push(flag.to_string());
+    }
+    Ok(flags)
+}
+
+impl Parse for Program {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let prelude = input.peek(Token![use]);
+        if prelude {
+            input.parse::<Token![use]>()?;
+            input.parse::<kw::prelude>()?;
+            input.parse::<Token![;]>()?;
+        }
+        let mut free = Vec::new();
+        if input.peek(kw::with) {
+            input.parse::<kw::with>()?;
+            loop {
+                let name: Ident = input.parse()?;
+                input.parse::<Token![:]>()?;
+                let tp: Tp = input.parse()?;
+                free.push((name.to_string(), tp));
+                if input.peek(Token![;]) {
+                    break;
+                }
+                input.parse::<Token![,]>()?;
+            }
+            input.parse::<Token![;]>()?;
+        }
+        let expr = input.parse()?;
+        Ok(Program {
+            opt: false,
+            prelude,
+            free,
+            expr,
+        })
+    }
 }
 
 impl Parse for Expr {
     fn parse(input: ParseStream) -> Result<Self> {
-        if input.peek(Token![let]) {
+        if input.peek(Token![let]) && input.peek2(syn::token::Brace) {
+            input.parse::<Token![let]>()?;
+            let content;
+            syn::braced!(content in input);
+            let tyvar: Ident = content.parse()?;
+            content.parse::<Token![,]>()?;
+            let name: Ident = content.parse()?;
+            input.parse::<Token![=]>()?;
+            let value = input.parse()?;
+            input.parse::<Token![in]>()?;
+            let body = input.parse()?;
+            Ok(Expr::Unpack {
+                tyvar: tyvar.to_string(),
+                name: name.to_string(),
+                value: Box::new(value),
+                body: Box::new(body),
+            })
+        } else if input.peek(Token![let]) {
             input.parse::<Token![let]>()?;
             let is_rec = input.peek(kw::rec);
             if is_rec {
@@ -89,15 +359,7 @@ impl Parse for Expr {
 
             // Check if this is a function definition with parameters
             if input.peek(syn::token::Paren) {
-                let mut params = Vec::new();
-                while input.peek(syn::token::Paren) {
-                    let content;
-                    syn::parenthesized!(content in input);
-                    let param: Ident = content.parse()?;
-                    content.parse::<Token![:]>()?;
-                    let tp: Tp = content.parse()?;
-                    params.push((param.to_string(), tp));
-                }
+                let params = parse_params(input)?;
 
                 let return_type = if input.peek(Token![:]) {
                     input.parse::<Token![:]>()?;
@@ -108,6 +370,46 @@ impl Parse for Expr {
 
                 input.parse::<Token![=]>()?;
                 let body = input.parse()?;
+
+                if is_rec && peek_rec_fn(input) {
+                    let return_type = return_type.ok_or_else(|| {
+                        syn::Error::new(name.span(), "recursive functions need a return type")
+                    })?;
+                    let mut fns = vec![RecFn {
+                        name: name.to_string(),
+                        params,
+                        return_type,
+                        body,
+                    }];
+                    while peek_rec_fn(input) {
+                        input.parse::<kw::and>()?;
+                        let name: Ident = input.parse()?;
+                        let params = parse_params(input)?;
+                        if params.is_empty() {
+                            return Err(syn::Error::new(
+                                name.span(),
+                                "recursive functions need at least one parameter",
+                            ));
+                        }
+                        input.parse::<Token![:]>()?;
+                        let return_type = input.parse()?;
+                        input.parse::<Token![=]>()?;
+                        let body = input.parse()?;
+                        fns.push(RecFn {
+                            name: name.to_string(),
+                            params,
+                            return_type,
+                            body,
+                        });
+                    }
+                    input.parse::<Token![in]>()?;
+                    let scope = input.parse()?;
+                    return Ok(Expr::LetRecGroup {
+                        fns,
+                        scope: Box::new(scope),
+                    });
+                }
+
                 input.parse::<Token![in]>()?;
                 let scope = input.parse()?;
 
@@ -143,11 +445,89 @@ impl Parse for Expr {
                 Box::new(then_branch),
                 Box::new(else_branch),
             ))
+        } else if input.peek(kw::pack) {
+            input.parse::<kw::pack>()?;
+            let hidden = input.parse()?;
+            input.parse::<Token![,]>()?;
+            let value = input.parse()?;
+            input.parse::<Token![as]>()?;
+            let tp = input.parse()?;
+            Ok(Expr::Pack {
+                hidden,
+                value: Box::new(value),
+                tp,
+            })
+        } else if input.peek(kw::case) {
+            input.parse::<kw::case>()?;
+            let scrutinee = input.parse()?;
+            input.parse::<kw::of>()?;
+            if input.peek(syn::token::Bracket) {
+                let content;
+                syn::bracketed!(content in input);
+                if !content.is_empty() {
+                    return Err(content.error("expected the empty list pattern []"));
+                }
+                input.parse::<Token![=>]>()?;
+                let nil = input.parse()?;
+                input.parse::<Token![|]>()?;
+                let head: Ident = input.parse()?;
+                input.parse::<Token![::]>()?;
+                let tail: Ident = input.parse()?;
+                input.parse::<Token![=>]>()?;
+                let cons = input.parse()?;
+                return Ok(Expr::ListCase {
+                    scrutinee: Box::new(scrutinee),
+                    nil: Box::new(nil),
+                    head: head.to_string(),
+                    tail: tail.to_string(),
+                    cons: Box::new(cons),
+                });
+            }
+            let mut branches: Vec<(String, String, Expr)> = Vec::new();
+            loop {
+                input.parse::<Token![<]>()?;
+                let ident: Ident = input.parse()?;
+                let label = ident.to_string();
+                if branches.iter().any(|(l, _, _)| *l == label) {
+                    return Err(syn::Error::new(ident.span(), "duplicate branch"));
+                }
+                input.parse::<Token![=]>()?;
+                let name: Ident = input.parse()?;
+                input.parse::<Token![>]>()?;
+                input.parse::<Token![=>]>()?;
+                let body = input.parse()?;
+                branches.push((label, name.to_string(), body));
+                if !input.peek(Token![|]) {
+                    break;
+                }
+                input.parse::<Token![|]>()?;
+            }
+            Ok(Expr::Match {
+                scrutinee: Box::new(scrutinee),
+                branches,
+            })
+        } else if input.peek(Token![try]) {
+            input.parse::<Token![try]>()?;
+            let body = input.parse()?;
+            input.parse::<kw::with>()?;
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=>]>()?;
+            let handler = input.parse()?;
+            Ok(Expr::Try {
+                body: Box::new(body),
+                name: name.to_string(),
+                handler: Box::new(handler),
+            })
         } else if input.peek(Token![fn]) {
             input.parse::<Token![fn]>()?;
             let param: Ident = input.parse()?;
-            input.parse::<Token![:]>()?;
-            let tp: Tp = input.parse()?;
+            // The parameter type can be omitted if it is given by an annotation
+            let tp = if input.peek(Token![:]) {
+                input.parse::<Token![:]>()?;
+                Some(input.parse::<Tp>()?)
+            } else {
+                None
+            };
             input.parse::<Token![=>]>()?;
             let body = input.parse()?;
             Ok(Expr::Lam {
@@ -156,11 +536,50 @@ impl Parse for Expr {
                 body: Box::new(body),
             })
         } else {
-            parse_application(input)
+            let mut expr = parse_cons(input)?;
+            // Assignment l := e
+            if input.peek(Token![:]) && input.peek2(Token![=]) {
+                input.parse::<Token![:]>()?;
+                input.parse::<Token![=]>()?;
+                let value = parse_cons(input)?;
+                expr = Expr::Assign(Box::new(expr), Box::new(value));
+            }
+            if input.peek(Token![;]) {
+                input.parse::<Token![;]>()?;
+                let rest = input.parse()?;
+                expr = Expr::Seq(Box::new(expr), Box::new(rest));
+            }
+            Ok(expr)
         }
     }
 }
 
+/// Parses the parameters `(x: Tp) (y: Tp) ...` of a function definition
+fn parse_params(input: ParseStream) -> Result<Vec<(String, Tp)>> {
+    let mut params = Vec::new();
+    while input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in input);
+        let param: Ident = content.parse()?;
+        content.parse::<Token![:]>()?;
+        let tp: Tp = content.parse()?;
+        params.push((param.to_string(), tp));
+    }
+    Ok(params)
+}
+
+/// Applications separated by `::`, which associates to the right
+fn parse_cons(input: ParseStream) -> Result<Expr> {
+    let head = parse_application(input)?;
+    if input.peek(Token![::]) {
+        input.parse::<Token![::]>()?;
+        let tail = parse_cons(input)?;
+        Ok(Expr::Cons(Box::new(head), Box::new(tail)))
+    } else {
+        Ok(head)
+    }
+}
+
 fn parse_application(input: ParseStream) -> Result<Expr> {
     let mut expr = parse_atom(input)?;
 
@@ -168,6 +587,15 @@ fn parse_application(input: ParseStream) -> Result<Expr> {
         && !input.peek(Token![in])
         && !input.peek(kw::then)
         && !input.peek(Token![else])
+        && !input.peek(Token![:])
+        && !peek_rec_fn(input)
+        && !input.peek(kw::with)
+        && !input.peek(Token![;])
+        && !input.peek(Token![,])
+        && !input.peek(Token![>])
+        && !input.peek(Token![|])
+        && !input.peek(kw::of)
+        && !input.peek(Token![as])
     {
         let arg = parse_atom(input)?;
         expr = Expr::App(Box::new(expr), Box::new(arg));
@@ -176,22 +604,95 @@ fn parse_application(input: ParseStream) -> Result<Expr> {
     Ok(expr)
 }
 
+/// Whether the input starts with the next function `and f (x: Tp) ... : Tp =`
+/// of a let rec group, elsewhere `and` is an ordinary name
+fn peek_rec_fn(input: ParseStream) -> bool {
+    let ahead = input.fork();
+    ahead.parse::<kw::and>().is_ok()
+        && ahead.parse::<Ident>().is_ok()
+        && parse_params(&ahead).is_ok()
+        && ahead.parse::<Token![:]>().is_ok()
+        && ahead.parse::<Tp>().is_ok()
+        && ahead.peek(Token![=])
+}
+
 fn eta_expand(tp: Tp, expr: impl Fn(Box<Expr>) -> Expr) -> Expr {
     Expr::Lam {
         param: "x".to_string(),
-        tp,
+        tp: Some(tp),
         body: Box::new(expr(Box::new(Expr::Var("x".to_string())))),
     }
 }
 
+/// An atom followed by any number of projections `.x`
 fn parse_atom(input: ParseStream) -> Result<Expr> {
+    let mut expr = parse_primary(input)?;
+    while input.peek(Token![.]) {
+        input.parse::<Token![.]>()?;
+        let field: Ident = input.parse()?;
+        expr = Expr::Proj(Box::new(expr), field.to_string());
+    }
+    Ok(expr)
+}
+
+fn parse_primary(input: ParseStream) -> Result<Expr> {
     if input.peek(LitBool) {
         let b: LitBool = input.parse()?;
         Ok(Expr::Bool(b.value))
     } else if input.peek(syn::token::Paren) {
         let content;
         syn::parenthesized!(content in input);
-        content.parse()
+        if content.is_empty() {
+            return Ok(Expr::Unit);
+        }
+        let expr = content.parse()?;
+        // Type ascription (e : Tp)
+        if content.peek(Token![:]) {
+            content.parse::<Token![:]>()?;
+            let tp = content.parse()?;
+            Ok(Expr::Ann(Box::new(expr), tp))
+        } else {
+            Ok(expr)
+        }
+    } else if input.peek(syn::token::Brace) {
+        let content;
+        syn::braced!(content in input);
+        let fields = parse_fields(&content, |content| {
+            content.parse::<Token![=]>()?;
+            content.parse()
+        })?;
+        Ok(Expr::Record(fields))
+    } else if input.peek(syn::token::Bracket) {
+        let content;
+        syn::bracketed!(content in input);
+        let elems = content.parse_terminated(Expr::parse, Token![,])?;
+        Ok(Expr::List(elems.into_iter().collect()))
+    } else if input.peek(Token![<]) {
+        input.parse::<Token![<]>()?;
+        let label: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        input.parse::<Token![>]>()?;
+        input.parse::<Token![as]>()?;
+        let tp = input.parse()?;
+        Ok(Expr::Tag {
+            label: label.to_string(),
+            value: Box::new(value),
+            tp,
+        })
+    } else if input.peek(Token![$]) {
+        // Antiquotation, the term is a Rust type which must be closed
+        input.parse::<Token![$]>()?;
+        let path: syn::Path = input.parse()?;
+        Ok(Expr::Splice(syn::Type::Path(syn::TypePath {
+            qself: None,
+            path,
+        })))
+    } else if input.peek(Token![#]) && input.peek2(syn::token::Brace) {
+        input.parse::<Token![#]>()?;
+        let content;
+        syn::braced!(content in input);
+        Ok(Expr::Splice(content.parse()?))
     } else if input.peek(LitInt) {
         let n: LitInt = input.parse()?;
         let value = n.base10_parse::<u64>()?;
@@ -213,6 +714,40 @@ fn parse_atom(input: ParseStream) -> Result<Expr> {
         input.parse::<kw::fix>()?;
         let arg = input.parse()?;
         Ok(Expr::Fix(Box::new(arg)))
+    } else if input.peek(Token![ref]) {
+        input.parse::<Token![ref]>()?;
+        let arg = input.parse()?;
+        Ok(Expr::Ref(Box::new(arg)))
+    } else if input.peek(Token![!]) {
+        input.parse::<Token![!]>()?;
+        let arg = parse_atom(input)?;
+        Ok(Expr::Deref(Box::new(arg)))
+    } else if input.peek(kw::print) {
+        input.parse::<kw::print>()?;
+        // Only takes an application, so that `print e1; e2` prints e1
+        let arg = parse_application(input)?;
+        Ok(Expr::Print(Box::new(arg)))
+    } else if input.peek(kw::raise) {
+        input.parse::<kw::raise>()?;
+        let arg = input.parse()?;
+        Ok(Expr::Raise(Box::new(arg)))
+    } else if input.peek(kw::callcc) {
+        input.parse::<kw::callcc>()?;
+        let arg = input.parse()?;
+        Ok(Expr::CallCC(Box::new(arg)))
+    } else if input.peek(kw::throw) {
+        input.parse::<kw::throw>()?;
+        let k = parse_atom(input)?;
+        let v = parse_atom(input)?;
+        Ok(Expr::Throw(Box::new(k), Box::new(v)))
+    } else if input.peek(kw::natrec) {
+        input.parse::<kw::natrec>()?;
+        // natrec takes its three arguments directly, since it cannot
+        // be eta-expanded without knowing the type of the result
+        let z = parse_atom(input)?;
+        let s = parse_atom(input)?;
+        let n = parse_atom(input)?;
+        Ok(Expr::NatRec(Box::new(z), Box::new(s), Box::new(n)))
     } else {
         let ident: Ident = input.parse()?;
         Ok(Expr::Var(ident.to_string()))
@@ -222,24 +757,136 @@ fn parse_atom(input: ParseStream) -> Result<Expr> {
 /// AST but where names have been replaced by de Bruijn indices.
 /// It could be generated directly at parse time but doing
 /// it in two steps is a bit simpler.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum DBExpr {
+    Unit,
     True,
     False,
     Nat(u64),
     Var(usize),
     If(Box<DBExpr>, Box<DBExpr>, Box<DBExpr>),
     Lam(Tp, Box<DBExpr>),
+    CLam(Box<DBExpr>),
     App(Box<DBExpr>, Box<DBExpr>),
+    Ann(Box<DBExpr>, Tp),
     Let(Box<DBExpr>, Box<DBExpr>),
     Fix(Box<DBExpr>),
+    /// The function at the given position in a group of mutually
+    /// recursive functions, each abstracted over the whole group
+    FixN(Vec<(Tp, DBExpr)>, usize),
+    NatRec(Box<DBExpr>, Box<DBExpr>, Box<DBExpr>),
+    Raise(Box<DBExpr>),
+    Try(Box<DBExpr>, Box<DBExpr>),
+    Ref(Box<DBExpr>),
+    Deref(Box<DBExpr>),
+    Assign(Box<DBExpr>, Box<DBExpr>),
+    Print(Box<DBExpr>),
+    CallCC(Box<DBExpr>),
+    Throw(Box<DBExpr>, Box<DBExpr>),
+    Record(Vec<(String, DBExpr)>),
+    Proj(Box<DBExpr>, String),
+    Tag(String, Box<DBExpr>, Tp),
+    Match(Box<DBExpr>, Vec<(String, DBExpr)>),
+    CNil,
+    Cons(Box<DBExpr>, Box<DBExpr>),
+    ListCase(Box<DBExpr>, Box<DBExpr>, Box<DBExpr>),
+    Pack(Tp, Box<DBExpr>, Tp),
+    Unpack(Box<DBExpr>, Box<DBExpr>),
     IsZero(Box<DBExpr>),
     Succ(Box<DBExpr>),
     Pred(Box<DBExpr>),
+    /// A term given by a Rust type, which `Closed` requires to be closed so
+    /// that it needs no shifting under binders
+    Splice(syn::Type),
+    /// A definition of stlc_defs!, which is closed as well. Its term is
+    /// only known for the prelude, where stlc_eval! and #![opt] use it.
+    Def(syn::Path, Option<Box<DBExpr>>),
+}
+
+impl DBExpr {
+    /// Rebuilds the term from its immediate subterms mapped by f, which
+    /// also gets the number of variables and of type variables each of
+    /// them binds
+    fn map_children(&self, mut f: impl FnMut(&DBExpr, usize, usize) -> DBExpr) -> DBExpr {
+        let mut g = |e: &DBExpr, k| Box::new(f(e, k, 0));
+        match self {
+            DBExpr::Unit
+            | DBExpr::True
+            | DBExpr::False
+            | DBExpr::Nat(_)
+            | DBExpr::Var(_)
+            | DBExpr::CNil => self.clone(),
+            DBExpr::If(c, t, e) => DBExpr::If(g(c, 0), g(t, 0), g(e, 0)),
+            DBExpr::Lam(tp, b) => DBExpr::Lam(tp.clone(), g(b, 1)),
+            DBExpr::CLam(b) => DBExpr::CLam(g(b, 1)),
+            DBExpr::App(a, b) => DBExpr::App(g(a, 0), g(b, 0)),
+            DBExpr::Ann(a, tp) => DBExpr::Ann(g(a, 0), tp.clone()),
+            DBExpr::Let(a, b) => DBExpr::Let(g(a, 0), g(b, 1)),
+            DBExpr::Fix(a) => DBExpr::Fix(g(a, 0)),
+            DBExpr::FixN(fns, k) => DBExpr::FixN(
+                fns.iter().map(|(tp, e)| (tp.clone(), *g(e, 0))).collect(),
+                *k,
+            ),
+            DBExpr::NatRec(z, s, n) => DBExpr::NatRec(g(z, 0), g(s, 0), g(n, 0)),
+            DBExpr::Raise(a) => DBExpr::Raise(g(a, 0)),
+            DBExpr::Try(a, h) => DBExpr::Try(g(a, 0), g(h, 1)),
+            DBExpr::Ref(a) => DBExpr::Ref(g(a, 0)),
+            DBExpr::Deref(a) => DBExpr::Deref(g(a, 0)),
+            DBExpr::Assign(a, b) => DBExpr::Assign(g(a, 0), g(b, 0)),
+            DBExpr::Print(a) => DBExpr::Print(g(a, 0)),
+            DBExpr::CallCC(a) => DBExpr::CallCC(g(a, 0)),
+            DBExpr::Throw(a, b) => DBExpr::Throw(g(a, 0), g(b, 0)),
+            DBExpr::Record(fs) => {
+                DBExpr::Record(fs.iter().map(|(l, e)| (l.clone(), *g(e, 0))).collect())
+            }
+            DBExpr::Proj(a, l) => DBExpr::Proj(g(a, 0), l.clone()),
+            DBExpr::Tag(l, a, tp) => DBExpr::Tag(l.clone(), g(a, 0), tp.clone()),
+            DBExpr::Match(a, bs) => DBExpr::Match(
+                g(a, 0),
+                bs.iter().map(|(l, b)| (l.clone(), *g(b, 1))).collect(),
+            ),
+            DBExpr::Cons(h, t) => DBExpr::Cons(g(h, 0), g(t, 0)),
+            // The head and the tail are bound in the cons branch
+            DBExpr::ListCase(l, n, c) => DBExpr::ListCase(g(l, 0), g(n, 0), g(c, 2)),
+            DBExpr::Pack(h, a, tp) => DBExpr::Pack(h.clone(), g(a, 0), tp.clone()),
+            // The body binds the hidden type as well as the term
+            DBExpr::Unpack(a, b) => DBExpr::Unpack(g(a, 0), Box::new(f(b, 1, 1))),
+            DBExpr::IsZero(a) => DBExpr::IsZero(g(a, 0)),
+            DBExpr::Succ(a) => DBExpr::Succ(g(a, 0)),
+            DBExpr::Pred(a) => DBExpr::Pred(g(a, 0)),
+            DBExpr::Splice(_) | DBExpr::Def(..) => self.clone(),
+        }
+    }
+
+    /// Rebuilds the term, replacing each variable Var(i) found under c
+    /// binders by f(i, c)
+    fn map_vars(&self, c: usize, f: &impl Fn(usize, usize) -> DBExpr) -> DBExpr {
+        match self {
+            DBExpr::Var(i) => f(*i, c),
+            _ => self.map_children(|e, k, _| e.map_vars(c + k, f)),
+        }
+    }
+
+    /// Rebuilds the term, replacing each type tp it annotates found under d
+    /// type binders by f(tp, d)
+    fn map_tys(&self, d: usize, f: &impl Fn(&Tp, usize) -> Tp) -> DBExpr {
+        match self.map_children(|e, _, k| e.map_tys(d + k, f)) {
+            DBExpr::Lam(tp, b) => DBExpr::Lam(f(&tp, d), b),
+            DBExpr::Ann(a, tp) => DBExpr::Ann(a, f(&tp, d)),
+            DBExpr::FixN(fns, k) => {
+                DBExpr::FixN(fns.into_iter().map(|(tp, e)| (f(&tp, d), e)).collect(), k)
+            }
+            DBExpr::Tag(l, a, tp) => DBExpr::Tag(l, a, f(&tp, d)),
+            DBExpr::Pack(h, a, tp) => DBExpr::Pack(f(&h, d), a, f(&tp, d)),
+            e => e,
+        }
+    }
 }
 
 /// Lower an Expr into a DBExpr, in the environment
 fn lower(expr: &Expr, env: &mut Vec<String>) -> DBExpr {
     match expr {
+        Expr::Unit => DBExpr::Unit,
         Expr::Bool(true) => DBExpr::True,
         Expr::Bool(false) => DBExpr::False,
         Expr::Nat(n) => DBExpr::Nat(*n),
@@ -248,9 +895,16 @@ fn lower(expr: &Expr, env: &mut Vec<String>) -> DBExpr {
             let idx = env
                 .iter()
                 .rev()
-                .position(|n| n == name)
-                .expect("unbound variable");
-            DBExpr::Var(idx)
+                .filter(|n| !is_tyvar(n))
+                .position(|n| n == name);
+            match idx {
+                Some(idx) => DBExpr::Var(idx),
+                // Names bound by no binder are the definitions of stlc_defs!
+                None => {
+                    let name = Ident::new(name, proc_macro2::Span::call_site());
+                    DBExpr::Def(name.into(), None)
+                }
+            }
         }
 
         Expr::If(c, t, e) => DBExpr::If(
@@ -263,10 +917,14 @@ fn lower(expr: &Expr, env: &mut Vec<String>) -> DBExpr {
             env.push(param.clone());
             let b = lower(body, env);
             env.pop();
-            DBExpr::Lam(tp.clone(), Box::new(b))
+            match tp {
+                Some(tp) => DBExpr::Lam(resolve_type(tp, env), Box::new(b)),
+                None => DBExpr::CLam(Box::new(b)),
+            }
         }
 
         Expr::App(f, x) => DBExpr::App(Box::new(lower(f, env)), Box::new(lower(x, env))),
+        Expr::Ann(e, tp) => DBExpr::Ann(Box::new(lower(e, env)), resolve_type(tp, env)),
 
         Expr::Let { name, value, body } => {
             let v = lower(value, env);
@@ -308,15 +966,15 @@ fn lower(expr: &Expr, env: &mut Vec<String>) -> DBExpr {
 
             // Convert to nested lambdas
             for (_param, tp) in params.iter().rev() {
-                func_expr = DBExpr::Lam(tp.clone(), Box::new(func_expr));
+                func_expr = DBExpr::Lam(resolve_type(tp, env), Box::new(func_expr));
             }
 
             if *is_rec {
                 // Create fix expression for recursive functions
                 let func_type = params.iter().rev().fold(
-                    return_type.clone().unwrap(),
+                    resolve_type(return_type.as_ref().unwrap(), env),
                     |acc: Tp, (_, tp): &(String, Tp)| {
-                        Tp::Arrow(Box::new(tp.clone()), Box::new(acc))
+                        Tp::Arrow(Box::new(resolve_type(tp, env)), Box::new(acc))
                     },
                 );
                 func_expr = DBExpr::Fix(Box::new(DBExpr::Lam(func_type, Box::new(func_expr))));
@@ -329,17 +987,228 @@ fn lower(expr: &Expr, env: &mut Vec<String>) -> DBExpr {
             env.pop();
             DBExpr::Let(Box::new(v), Box::new(b))
         }
+        Expr::LetRecGroup { fns, scope } => {
+            // The group is lowered once and its functions are bound together
+            // by a single let, as the fields of a record labelled by their
+            // position. Binding them with a let each would shift the
+            // environment, and so the group, for each of them.
+            let group = lower_group(fns, env);
+            let n = fns.len();
+            let record = (0..n)
+                .map(|k| (k.to_string(), DBExpr::FixN(group.clone(), k)))
+                .collect();
+            for f in fns {
+                env.push(f.name.clone());
+            }
+            let scope = lower(scope, env);
+            for _ in fns {
+                env.pop();
+            }
+            // The scope is lowered with a variable for each function, which
+            // become projections of the record
+            let scope = scope.map_vars(0, &|i, c| match i.checked_sub(c) {
+                Some(j) if j < n => DBExpr::Proj(Box::new(DBExpr::Var(c)), (n - 1 - j).to_string()),
+                Some(_) => DBExpr::Var(i + 1 - n),
+                None => DBExpr::Var(i),
+            });
+            DBExpr::Let(Box::new(DBExpr::Record(record)), Box::new(scope))
+        }
         Expr::Fix(t) => {
             let t = lower(t, env);
             DBExpr::Fix(Box::new(t))
         }
+        Expr::NatRec(z, s, n) => DBExpr::NatRec(
+            Box::new(lower(z, env)),
+            Box::new(lower(s, env)),
+            Box::new(lower(n, env)),
+        ),
+        Expr::Raise(e) => DBExpr::Raise(Box::new(lower(e, env))),
+        Expr::Ref(e) => DBExpr::Ref(Box::new(lower(e, env))),
+        Expr::Deref(e) => DBExpr::Deref(Box::new(lower(e, env))),
+        Expr::Assign(l, e) => DBExpr::Assign(Box::new(lower(l, env)), Box::new(lower(e, env))),
+        Expr::Print(e) => DBExpr::Print(Box::new(lower(e, env))),
+        Expr::CallCC(e) => DBExpr::CallCC(Box::new(lower(e, env))),
+        Expr::Throw(k, v) => DBExpr::Throw(Box::new(lower(k, env)), Box::new(lower(v, env))),
+        Expr::Record(fields) => DBExpr::Record(
+            fields
+                .iter()
+                .map(|(name, e)| (name.clone(), lower(e, env)))
+                .collect(),
+        ),
+        Expr::Proj(e, field) => DBExpr::Proj(Box::new(lower(e, env)), field.clone()),
+        Expr::Tag { label, value, tp } => {
+            let value = lower(value, env);
+            DBExpr::Tag(label.clone(), Box::new(value), resolve_type(tp, env))
+        }
+        Expr::Match {
+            scrutinee,
+            branches,
+        } => {
+            let scrutinee = lower(scrutinee, env);
+            let branches = branches
+                .iter()
+                .map(|(label, name, body)| {
+                    env.push(name.clone());
+                    let body = lower(body, env);
+                    env.pop();
+                    (label.clone(), body)
+                })
+                .collect();
+            DBExpr::Match(Box::new(scrutinee), branches)
+        }
+        Expr::Pack { hidden, value, tp } => {
+            let value = lower(value, env);
+            DBExpr::Pack(
+                resolve_type(hidden, env),
+                Box::new(value),
+                resolve_type(tp, env),
+            )
+        }
+        Expr::Unpack {
+            tyvar,
+            name,
+            value,
+            body,
+        } => {
+            let value = lower(value, env);
+            env.push(format!("'{tyvar}"));
+            env.push(name.clone());
+            let body = lower(body, env);
+            env.pop();
+            env.pop();
+            DBExpr::Unpack(Box::new(value), Box::new(body))
+        }
+        Expr::List(elems) => elems.iter().rev().fold(DBExpr::CNil, |acc, e| {
+            DBExpr::Cons(Box::new(lower(e, env)), Box::new(acc))
+        }),
+        Expr::Cons(h, t) => DBExpr::Cons(Box::new(lower(h, env)), Box::new(lower(t, env))),
+        Expr::ListCase {
+            scrutinee,
+            nil,
+            head,
+            tail,
+            cons,
+        } => {
+            let scrutinee = lower(scrutinee, env);
+            let nil = lower(nil, env);
+            env.push(head.clone());
+            env.push(tail.clone());
+            let cons = lower(cons, env);
+            env.pop();
+            env.pop();
+            DBExpr::ListCase(Box::new(scrutinee), Box::new(nil), Box::new(cons))
+        }
+        Expr::Seq(e1, e2) => {
+            let e1 = lower(e1, env);
+            // The discarded value is bound to a name that cannot appear in the program
+            env.push("_".to_string());
+            let e2 = lower(e2, env);
+            env.pop();
+            DBExpr::Let(Box::new(e1), Box::new(e2))
+        }
+        Expr::Try {
+            body,
+            name,
+            handler,
+        } => {
+            let b = lower(body, env);
+            env.push(name.clone());
+            let h = lower(handler, env);
+            env.pop();
+            DBExpr::Try(Box::new(b), Box::new(h))
+        }
 
         Expr::IsZero(expr) => DBExpr::IsZero(Box::new(lower(expr, env))),
         Expr::Succ(expr) => DBExpr::Succ(Box::new(lower(expr, env))),
         Expr::Pred(expr) => DBExpr::Pred(Box::new(lower(expr, env))),
+        Expr::Splice(ty) => DBExpr::Splice(ty.clone()),
+        Expr::Def(path, term) => DBExpr::Def(path.clone(), Some(term.clone())),
     }
 }
 
+// Type variables share the environment with term variables, they are
+// marked with a leading quote (which cannot start an identifier) and
+// are counted separately since they have their own indices.
+fn is_tyvar(name: &str) -> bool {
+    name.starts_with('\'')
+}
+
+/// Replaces the names of the type variables of a type by their index
+fn resolve_type(tp: &Tp, env: &mut Vec<String>) -> Tp {
+    let fields = |fields: &[(String, Tp)], env: &mut Vec<String>| {
+        fields
+            .iter()
+            .map(|(name, tp)| (name.clone(), resolve_type(tp, env)))
+            .collect()
+    };
+    match tp {
+        Tp::Bool | Tp::Nat | Tp::Unit | Tp::Top | Tp::Dyn | Tp::TVar(_) => tp.clone(),
+        Tp::Ref(tp) => Tp::Ref(Box::new(resolve_type(tp, env))),
+        Tp::Cont(tp) => Tp::Cont(Box::new(resolve_type(tp, env))),
+        Tp::List(tp) => Tp::List(Box::new(resolve_type(tp, env))),
+        Tp::Record(fs) => Tp::Record(fields(fs, env)),
+        Tp::Variant(cs) => Tp::Variant(fields(cs, env)),
+        Tp::Arrow(from, to) => Tp::Arrow(
+            Box::new(resolve_type(from, env)),
+            Box::new(resolve_type(to, env)),
+        ),
+        Tp::Var(name) => {
+            let tyvar = format!("'{name}");
+            let idx = env
+                .iter()
+                .rev()
+                .filter(|n| is_tyvar(n))
+                .position(|n| *n == tyvar)
+                .expect("unbound type variable");
+            Tp::TVar(idx)
+        }
+        Tp::Exists(name, tp) => {
+            env.push(format!("'{name}"));
+            let tp = resolve_type(tp, env);
+            env.pop();
+            Tp::Exists(name.clone(), Box::new(tp))
+        }
+    }
+}
+
+/// Lowers each function of a group, abstracted over all the functions
+/// of the group (the first one being bound by the outermost lambda).
+fn lower_group(fns: &[RecFn], env: &mut Vec<String>) -> Vec<(Tp, DBExpr)> {
+    let fn_type = |f: &RecFn, env: &mut Vec<String>| {
+        f.params
+            .iter()
+            .rev()
+            .fold(resolve_type(&f.return_type, env), |acc, (_, tp)| {
+                Tp::Arrow(Box::new(resolve_type(tp, env)), Box::new(acc))
+            })
+    };
+
+    for f in fns {
+        env.push(f.name.clone());
+    }
+    let group = fns
+        .iter()
+        .map(|f| {
+            for (param, _) in f.params.iter() {
+                env.push(param.clone());
+            }
+            let mut body = lower(&f.body, env);
+            for (_, tp) in f.params.iter().rev() {
+                env.pop();
+                body = DBExpr::Lam(resolve_type(tp, env), Box::new(body));
+            }
+            for g in fns.iter().rev() {
+                body = DBExpr::Lam(fn_type(g, env), Box::new(body));
+            }
+            (fn_type(f, env), body)
+        })
+        .collect();
+    for _ in fns {
+        env.pop();
+    }
+    group
+}
+
 fn index_type(n: usize) -> proc_macro2::TokenStream {
     let mut ts = quote::quote! { I0 };
     for _ in 0..n {
@@ -348,21 +1217,156 @@ fn index_type(n: usize) -> proc_macro2::TokenStream {
     ts
 }
 
+/// The label of a record field, the bits of its name with the most significant first
+fn label_type(name: &str) -> proc_macro2::TokenStream {
+    let mut ts = quote::quote! { LEnd };
+    for byte in name.bytes().rev() {
+        for bit in 0..8 {
+            ts = if byte >> bit & 1 == 1 {
+                quote::quote! { L1<#ts> }
+            } else {
+                quote::quote! { L0<#ts> }
+            };
+        }
+    }
+    ts
+}
+
 fn expand_type(tp: &Tp) -> proc_macro2::TokenStream {
     match tp {
         Tp::Bool => quote::quote! { Bool },
         Tp::Nat => quote::quote! { Nat },
+        Tp::Unit => quote::quote! { Unit },
+        Tp::Top => quote::quote! { Top },
+        Tp::Dyn => quote::quote! { Dyn },
+        Tp::Ref(tp) => {
+            let tp = expand_type(tp);
+            quote::quote! { RefTy<#tp> }
+        }
+        Tp::Cont(tp) => {
+            let tp = expand_type(tp);
+            quote::quote! { Cont<#tp> }
+        }
+        Tp::List(tp) => {
+            let tp = expand_type(tp);
+            quote::quote! { List<#tp> }
+        }
+        Tp::Record(fields) => {
+            let fields =
+                fields
+                    .iter()
+                    .rev()
+                    .fold(quote::quote! { FieldTyNil }, |acc, (name, tp)| {
+                        let label = label_type(name);
+                        let tp = expand_type(tp);
+                        quote::quote! { FieldTyCons<#label, #tp, #acc> }
+                    });
+            quote::quote! { RecordTy<#fields> }
+        }
+        Tp::Variant(cases) => {
+            let cases = cases
+                .iter()
+                .rev()
+                .fold(quote::quote! { FieldTyNil }, |acc, (name, tp)| {
+                    let label = label_type(name);
+                    let tp = expand_type(tp);
+                    quote::quote! { FieldTyCons<#label, #tp, #acc> }
+                });
+            quote::quote! { VariantTy<#cases> }
+        }
         Tp::Arrow(from, to) => {
             let from = expand_type(from);
             let to = expand_type(to);
             quote::quote! { Arrow<#from, #to> }
         }
+        Tp::Var(name) => panic!("unresolved type variable {name}"),
+        Tp::TVar(idx) => {
+            let idx = index_type(*idx);
+            quote::quote! { TVar<#idx> }
+        }
+        Tp::Exists(_, tp) => {
+            let tp = expand_type(tp);
+            quote::quote! { Exists<#tp> }
+        }
     }
 }
 
+/// Builds the typing environment of the free variables, with the first one at its head
+fn expand_env(free: &[(String, Tp)]) -> proc_macro2::TokenStream {
+    free.iter()
+        .rev()
+        .fold(quote::quote! { EmptyEnv }, |acc, (_, tp)| {
+            let tp = expand_type(tp);
+            quote::quote! { TyCons<#tp, #acc> }
+        })
+}
+
+/// Hash-consing of the expansion, a subterm which occurs more than once is
+/// emitted once as a type alias that every occurrence refers to. The term is
+/// expanded twice, first counting the occurrences of each subterm and then
+/// emitting the aliases. Without a prefix nothing is shared, which is the
+/// case where the macro is used as a type and cannot emit items.
+#[derive(Default)]
+struct Sharing<'a> {
+    prefix: Option<String>,
+    counting: bool,
+    counts: HashMap<&'a DBExpr, usize>,
+    aliases: HashMap<&'a DBExpr, Ident>,
+    items: Vec<(Ident, proc_macro2::TokenStream)>,
+}
+
 impl DBExpr {
     fn expand(&self) -> proc_macro2::TokenStream {
+        self.expand_shared(&mut Sharing::default())
+    }
+
+    /// Terms which are not worth an alias
+    fn is_atomic(&self) -> bool {
+        matches!(
+            self,
+            DBExpr::Unit
+                | DBExpr::True
+                | DBExpr::False
+                | DBExpr::Var(_)
+                | DBExpr::CNil
+                | DBExpr::Nat(0)
+                | DBExpr::Splice(_)
+                | DBExpr::Def(..)
+        )
+    }
+
+    fn expand_shared<'a>(&'a self, sharing: &mut Sharing<'a>) -> proc_macro2::TokenStream {
+        let Some(prefix) = sharing.prefix.clone() else {
+            return self.expand_node(sharing);
+        };
+        if self.is_atomic() {
+            return self.expand_node(sharing);
+        }
+        if sharing.counting {
+            let count = sharing.counts.entry(self).or_insert(0);
+            *count += 1;
+            // The subterms of a repeated term are only counted once
+            if *count > 1 {
+                return proc_macro2::TokenStream::new();
+            }
+            return self.expand_node(sharing);
+        }
+        if sharing.counts.get(self).copied().unwrap_or(0) < 2 {
+            return self.expand_node(sharing);
+        }
+        if let Some(alias) = sharing.aliases.get(self) {
+            return quote::quote! { #alias };
+        }
+        let ty = self.expand_node(sharing);
+        let alias = quote::format_ident!("__stlc_{}_{}", prefix, sharing.items.len());
+        sharing.items.push((alias.clone(), ty));
+        sharing.aliases.insert(self, alias.clone());
+        quote::quote! { #alias }
+    }
+
+    fn expand_node<'a>(&'a self, sharing: &mut Sharing<'a>) -> proc_macro2::TokenStream {
         match self {
+            DBExpr::Unit => quote::quote! { Triv },
             DBExpr::True => quote::quote! { True },
             DBExpr::False => quote::quote! { False },
             DBExpr::Nat(n) => {
@@ -379,78 +1383,663 @@ impl DBExpr {
             }
 
             DBExpr::If(c, t, e) => {
-                let c = c.expand();
-                let t = t.expand();
-                let e = e.expand();
+                let c = c.expand_shared(sharing);
+                let t = t.expand_shared(sharing);
+                let e = e.expand_shared(sharing);
                 quote::quote! {
                     If<#c, #t, #e>
                 }
             }
 
             DBExpr::Lam(tp, body) => {
-                let b = body.expand();
+                let b = body.expand_shared(sharing);
                 let tp_tokens = expand_type(tp);
                 quote::quote! {
                     Lam<#tp_tokens, #b>
                 }
             }
 
+            DBExpr::CLam(body) => {
+                let b = body.expand_shared(sharing);
+                quote::quote! {
+                    CLam<#b>
+                }
+            }
+
+            DBExpr::Ann(e, tp) => {
+                let e = e.expand_shared(sharing);
+                let tp_tokens = expand_type(tp);
+                quote::quote! {
+                    Ann<#e, #tp_tokens>
+                }
+            }
+
             DBExpr::App(f, x) => {
-                let f = f.expand();
-                let x = x.expand();
+                let f = f.expand_shared(sharing);
+                let x = x.expand_shared(sharing);
                 quote::quote! {
                     App<#f, #x>
                 }
             }
 
             DBExpr::Let(v, b) => {
-                let v = v.expand();
-                let b = b.expand();
+                let v = v.expand_shared(sharing);
+                let b = b.expand_shared(sharing);
                 quote::quote! {
                     Let<#v, #b>
                 }
             }
 
             DBExpr::Fix(t) => {
-                let t = t.expand();
+                let t = t.expand_shared(sharing);
                 quote::quote! {
                     Fix<#t>
                 }
             }
 
+            DBExpr::FixN(fns, k) => {
+                let fns = fns
+                    .iter()
+                    .rev()
+                    .fold(quote::quote! { FnNil }, |acc, (tp, f)| {
+                        let tp = expand_type(tp);
+                        let f = f.expand_shared(sharing);
+                        quote::quote! { FnCons<#tp, #f, #acc> }
+                    });
+                let k = index_type(*k);
+                quote::quote! {
+                    FixN<#fns, #k>
+                }
+            }
+
+            DBExpr::NatRec(z, s, n) => {
+                let z = z.expand_shared(sharing);
+                let s = s.expand_shared(sharing);
+                let n = n.expand_shared(sharing);
+                quote::quote! {
+                    NatRec<#z, #s, #n>
+                }
+            }
+
+            DBExpr::Raise(e) => {
+                let e = e.expand_shared(sharing);
+                quote::quote! {
+                    Raise<#e>
+                }
+            }
+
+            DBExpr::Ref(e) => {
+                let e = e.expand_shared(sharing);
+                quote::quote! {
+                    Ref<#e>
+                }
+            }
+
+            DBExpr::Deref(e) => {
+                let e = e.expand_shared(sharing);
+                quote::quote! {
+                    Deref<#e>
+                }
+            }
+
+            DBExpr::Assign(l, e) => {
+                let l = l.expand_shared(sharing);
+                let e = e.expand_shared(sharing);
+                quote::quote! {
+                    Assign<#l, #e>
+                }
+            }
+
+            DBExpr::Print(e) => {
+                let e = e.expand_shared(sharing);
+                quote::quote! {
+                    Print<#e>
+                }
+            }
+
+            DBExpr::Record(fields) => {
+                let fields =
+                    fields
+                        .iter()
+                        .rev()
+                        .fold(quote::quote! { FieldNil }, |acc, (name, e)| {
+                            let label = label_type(name);
+                            let e = e.expand_shared(sharing);
+                            quote::quote! { FieldCons<#label, #e, #acc> }
+                        });
+                quote::quote! {
+                    Record<#fields>
+                }
+            }
+
+            DBExpr::Proj(e, field) => {
+                let e = e.expand_shared(sharing);
+                let label = label_type(field);
+                quote::quote! {
+                    Proj<#e, #label>
+                }
+            }
+
+            DBExpr::Tag(label, e, tp) => {
+                let label = label_type(label);
+                let e = e.expand_shared(sharing);
+                let tp = expand_type(tp);
+                quote::quote! {
+                    Tag<#label, #e, #tp>
+                }
+            }
+
+            DBExpr::Match(e, branches) => {
+                let e = e.expand_shared(sharing);
+                let branches =
+                    branches
+                        .iter()
+                        .rev()
+                        .fold(quote::quote! { BranchNil }, |acc, (label, b)| {
+                            let label = label_type(label);
+                            let b = b.expand_shared(sharing);
+                            quote::quote! { BranchCons<#label, #b, #acc> }
+                        });
+                quote::quote! {
+                    Match<#e, #branches>
+                }
+            }
+
+            DBExpr::Pack(hidden, e, tp) => {
+                let hidden = expand_type(hidden);
+                let e = e.expand_shared(sharing);
+                let tp = expand_type(tp);
+                quote::quote! {
+                    Pack<#hidden, #e, #tp>
+                }
+            }
+
+            DBExpr::Unpack(e, b) => {
+                let e = e.expand_shared(sharing);
+                let b = b.expand_shared(sharing);
+                quote::quote! {
+                    Unpack<#e, #b>
+                }
+            }
+
+            DBExpr::CNil => quote::quote! { CNil },
+
+            DBExpr::Cons(h, t) => {
+                let h = h.expand_shared(sharing);
+                let t = t.expand_shared(sharing);
+                quote::quote! {
+                    Cons<#h, #t>
+                }
+            }
+
+            DBExpr::ListCase(l, n, c) => {
+                let l = l.expand_shared(sharing);
+                let n = n.expand_shared(sharing);
+                let c = c.expand_shared(sharing);
+                quote::quote! {
+                    ListCase<#l, #n, #c>
+                }
+            }
+
+            DBExpr::CallCC(e) => {
+                let e = e.expand_shared(sharing);
+                quote::quote! {
+                    CallCC<#e>
+                }
+            }
+
+            DBExpr::Throw(k, v) => {
+                let k = k.expand_shared(sharing);
+                let v = v.expand_shared(sharing);
+                quote::quote! {
+                    Throw<#k, #v>
+                }
+            }
+
+            DBExpr::Try(b, h) => {
+                let b = b.expand_shared(sharing);
+                let h = h.expand_shared(sharing);
+                quote::quote! {
+                    Try<#b, #h>
+                }
+            }
+
             DBExpr::IsZero(expr) => {
-                let e = expr.expand();
+                let e = expr.expand_shared(sharing);
                 quote::quote! {
                     IsZero<#e>
                 }
             }
 
             DBExpr::Succ(expr) => {
-                let e = expr.expand();
+                let e = expr.expand_shared(sharing);
                 quote::quote! {
                     Succ<#e>
                 }
             }
 
             DBExpr::Pred(expr) => {
-                let e = expr.expand();
+                let e = expr.expand_shared(sharing);
                 quote::quote! {
                     Pred<#e>
                 }
             }
+
+            DBExpr::Splice(ty) => quote::quote! { <#ty as Closed>::Res },
+
+            // The array is only of length 0 when the constant of the same
+            // name is the marker of the definition
+            DBExpr::Def(path, _) => quote::quote! {
+                <[DefMarker<#path>; { let _: DefMarker<#path> = #path; 0 }] as Definition>::Res
+            },
+        }
+    }
+}
+
+/// The macro is either used as a type, or as an item `type Name = ...;`
+/// binding the program to a type alias
+enum Input {
+    Term(Program),
+    Def {
+        vis: syn::Visibility,
+        name: Ident,
+        program: Program,
+    },
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let opt = !parse_flags(input, &["opt"])?.is_empty();
+        if input.peek(Token![type]) || input.peek(Token![pub]) {
+            let vis = input.parse()?;
+            input.parse::<Token![type]>()?;
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let program = Program {
+                opt,
+                ..input.parse()?
+            };
+            if input.peek(Token![;]) {
+                input.parse::<Token![;]>()?;
+            }
+            Ok(Input::Def { vis, name, program })
+        } else {
+            Ok(Input::Term(Program {
+                opt,
+                ..input.parse()?
+            }))
         }
     }
 }
 
+/// Lowers a program, binding the definitions of the prelude it uses and
+/// optimizing it when asked to
+fn lower_program(
+    Program {
+        opt,
+        prelude,
+        free,
+        expr,
+    }: &Program,
+) -> DBExpr {
+    // The first declared variable gets index 0, so it is pushed last
+    let mut env: Vec<String> = free.iter().rev().map(|(name, _)| name.clone()).collect();
+    let mut db = lower(expr, &mut env);
+    if *prelude {
+        db = lower(&with_prelude(expr, &db), &mut env);
+    }
+    if *opt {
+        let tenv: Vec<Tp> = free.iter().rev().map(|(_, tp)| tp.clone()).collect();
+        db = opt::optimize(db, &tenv);
+    }
+    db
+}
+
+fn expand_program(
+    program: &Program,
+    prefix: Option<String>,
+) -> (
+    proc_macro2::TokenStream,
+    Vec<(Ident, proc_macro2::TokenStream)>,
+) {
+    let (ty, items) = expand_term(&lower_program(program), prefix);
+
+    let ty = if program.free.is_empty() {
+        ty
+    } else {
+        let tenv = expand_env(&program.free);
+        quote::quote! { Open<#tenv, #ty> }
+    };
+    (ty, items)
+}
+
+/// Binds the definitions of the prelude the program refers to, which are
+/// the ones referred to by its lowering db, with lets around it
+fn with_prelude(expr: &Expr, db: &DBExpr) -> Expr {
+    prelude::terms()
+        .into_iter()
+        .rev()
+        .filter(|(name, _)| db.refers_to(name))
+        .fold(expr.clone(), |body, (name, term)| Expr::Let {
+            name: name.to_string(),
+            value: Box::new(Expr::Def(
+                syn::parse_quote! { prelude::#name },
+                Box::new(term),
+            )),
+            body: Box::new(body),
+        })
+}
+
+/// Expands a term, sharing its repeated subterms as aliases when it is
+/// given a prefix for their names
+fn expand_term(
+    db: &DBExpr,
+    prefix: Option<String>,
+) -> (
+    proc_macro2::TokenStream,
+    Vec<(Ident, proc_macro2::TokenStream)>,
+) {
+    let mut sharing = Sharing {
+        prefix,
+        counting: true,
+        ..Sharing::default()
+    };
+    if sharing.prefix.is_some() {
+        db.expand_shared(&mut sharing);
+        sharing.counting = false;
+    }
+    let ty = db.expand_shared(&mut sharing);
+    (ty, sharing.items)
+}
+
 #[proc_macro]
 pub fn stlc(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let expr = syn::parse_macro_input!(input as Expr);
-
-    let mut env = Vec::new();
-    let db = lower(&expr, &mut env);
-    let ty = db.expand();
+    match syn::parse_macro_input!(input as Input) {
+        Input::Term(program) => {
+            let (ty, _) = expand_program(&program, None);
+            proc_macro::TokenStream::from(ty)
+        }
+        Input::Def { vis, name, program } => {
+            let (ty, items) = expand_program(&program, Some(name.to_string()));
+            let aliases = items.iter().map(|(alias, ty)| {
+                quote::quote! {
+                    #[doc(hidden)]
+                    #[allow(non_camel_case_types)]
+                    #vis type #alias = #ty;
+                }
+            });
+            proc_macro::TokenStream::from(quote::quote! {
+                #(#aliases)*
+                #vis type #name = #ty;
+            })
+        }
+    }
+}
 
+// Untyped terms, written with the syntax of stlc! where annotations may be
+// omitted everywhere and are erased anyway
+#[proc_macro]
+pub fn ulc(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let expr = syn::parse_macro_input!(input as Expr);
+    let ty = lower(&expr, &mut Vec::new()).expand();
     proc_macro::TokenStream::from(quote::quote! {
-        #ty
+        <#ty as Erase>::Res
     })
 }
+
+/// Input of stlc_eval!, a closed program optionally preceded by the flags
+/// `#![verify]` and `#![opt]`, which is either used as a type or bound to
+/// a type alias by the item `type Name = ...;`
+struct EvalInput {
+    verify: bool,
+    item: Option<(syn::Visibility, Ident)>,
+    program: Program,
+}
+
+impl Parse for EvalInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let flags = parse_flags(input, &["verify", "opt"])?;
+        let item = if input.peek(Token![type]) || input.peek(Token![pub]) {
+            let vis = input.parse()?;
+            input.parse::<Token![type]>()?;
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Some((vis, name))
+        } else {
+            None
+        };
+        let program: Program = input.parse()?;
+        if !program.free.is_empty() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "stlc_eval! evaluates closed programs",
+            ));
+        }
+        if item.is_some() && input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+        }
+        Ok(EvalInput {
+            verify: flags.iter().any(|flag| flag == "verify"),
+            item,
+            program: Program {
+                opt: flags.iter().any(|flag| flag == "opt"),
+                ..program
+            },
+        })
+    }
+}
+
+// Type-checks and evaluates a closed program while expanding the macro,
+// which is much faster than having rustc do it, and gives its value.
+// With #![verify], rustc evaluates the program as well and checks that
+// it gets the same value, through an eval_to assertion when the macro is
+// an item since an unused type alias is never normalized.
+#[proc_macro]
+pub fn stlc_eval(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let EvalInput {
+        verify,
+        item,
+        program,
+    } = syn::parse_macro_input!(input as EvalInput);
+    let db = lower_program(&program);
+    let value = match native::synth(&mut Vec::new(), &db).and_then(|_| native::eval(&db)) {
+        Ok(value) => value.expand(),
+        Err(msg) => {
+            return syn::Error::new(proc_macro2::Span::call_site(), msg)
+                .to_compile_error()
+                .into();
+        }
+    };
+    let term = db.expand();
+    let tokens = match item {
+        Some((vis, name)) if verify => quote::quote! {
+            #vis type #name = #value;
+            const _: fn() = eval_to::<#term, #name>;
+        },
+        Some((vis, name)) => quote::quote! { #vis type #name = #value; },
+        None if verify => quote::quote! { <#value as EvalOf<#term>>::Res },
+        None => value,
+    };
+    proc_macro::TokenStream::from(tokens)
+}
+
+/// Input of names!, identifiers which may be preceded by a visibility
+struct Names {
+    vis: syn::Visibility,
+    names: syn::punctuated::Punctuated<Ident, Token![,]>,
+}
+
+impl Parse for Names {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Names {
+            vis: input.parse()?,
+            names: syn::punctuated::Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+// Declares the names used by the terms with named variables, each one is a
+// type alias for its label, `names!(x, f)` gives `type x = ...; type f = ...;`
+#[proc_macro]
+pub fn names(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let Names { vis, names } = syn::parse_macro_input!(input as Names);
+    let aliases = names.iter().map(|name| {
+        let label = label_type(&name.to_string());
+        quote::quote! {
+            #[allow(non_camel_case_types)]
+            #vis type #name = #label;
+        }
+    });
+    proc_macro::TokenStream::from(quote::quote! { #(#aliases)* })
+}
+
+/// A top-level definition `def name (x: Tp) ... : Tp = body;`
+struct Def {
+    name: Ident,
+    params: Vec<(String, Tp)>,
+    return_type: Tp,
+    body: Expr,
+}
+
+impl Parse for Def {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<kw::def>()?;
+        let name = input.parse()?;
+        let params = parse_params(input)?;
+        input.parse::<Token![:]>()?;
+        let return_type = input.parse()?;
+        input.parse::<Token![=]>()?;
+        // The body may be a sequence, so it is taken up to the `;` which
+        // ends the definition before being parsed
+        let body = input.step(|cursor| {
+            let mut rest = *cursor;
+            let mut body = proc_macro2::TokenStream::new();
+            while !ends_def(rest) {
+                let (tt, next) = rest
+                    .token_tree()
+                    .ok_or_else(|| cursor.error("expected `;` after the definition"))?;
+                body.extend([tt]);
+                rest = next;
+            }
+            Ok((body, rest))
+        })?;
+        let body = syn::parse2(body)?;
+        input.parse::<Token![;]>()?;
+        Ok(Def {
+            name,
+            params,
+            return_type,
+            body,
+        })
+    }
+}
+
+/// Whether the tokens at cursor are the `;` ending a definition, the one
+/// before the next definition or at the end of the input
+fn ends_def(cursor: syn::buffer::Cursor) -> bool {
+    match cursor.punct() {
+        Some((p, rest)) if p.as_char() == ';' => {
+            rest.eof() || rest.ident().is_some_and(|(ident, _)| ident == "def")
+        }
+        _ => false,
+    }
+}
+
+impl DBExpr {
+    /// Whether the definition `name` is referred to somewhere in the term
+    fn refers_to(&self, name: &Ident) -> bool {
+        match self {
+            DBExpr::Def(path, _) => path.is_ident(name),
+            _ => {
+                let found = std::cell::Cell::new(false);
+                self.map_children(|e, _, _| {
+                    found.set(found.get() || e.refers_to(name));
+                    e.clone()
+                });
+                found.get()
+            }
+        }
+    }
+}
+
+/// Lowers a definition to a closed term, which is recursive if its body
+/// refers to the definition itself
+fn lower_def(def: &Def) -> DBExpr {
+    let lower_fn = |is_rec| {
+        let expr = Expr::LetFn {
+            name: def.name.to_string(),
+            params: def.params.clone(),
+            return_type: Some(def.return_type.clone()),
+            body: Box::new(Expr::Ann(
+                Box::new(def.body.clone()),
+                def.return_type.clone(),
+            )),
+            scope: Box::new(Expr::Var(def.name.to_string())),
+            is_rec,
+        };
+        match lower(&expr, &mut Vec::new()) {
+            DBExpr::Let(v, _) => *v,
+            _ => unreachable!("a function definition lowers to a let"),
+        }
+    };
+    let term = lower_fn(false);
+    if term.refers_to(&def.name) {
+        lower_fn(true)
+    } else {
+        term
+    }
+}
+
+// Top-level definitions, each one becomes a public type alias which later
+// programs can refer to by its name, and is checked to have its declared type
+#[proc_macro]
+pub fn stlc_defs(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let defs = syn::parse_macro_input!(input with parse_defs);
+    proc_macro::TokenStream::from(expand_defs(&defs))
+}
+
+// The definitions of the prelude, for the prelude module of stlcrts
+#[doc(hidden)]
+#[proc_macro]
+pub fn prelude_defs(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    proc_macro::TokenStream::from(expand_defs(&prelude::defs()))
+}
+
+fn parse_defs(input: ParseStream) -> Result<Vec<Def>> {
+    let mut defs = Vec::new();
+    while !input.is_empty() {
+        defs.push(input.parse::<Def>()?);
+    }
+    Ok(defs)
+}
+
+fn expand_defs(defs: &[Def]) -> proc_macro2::TokenStream {
+    let items = defs.iter().map(|def| {
+        let name = &def.name;
+        let (ty, items) = expand_term(&lower_def(def), Some(name.to_string()));
+        let aliases = items.iter().map(|(alias, ty)| {
+            quote::quote! {
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                pub type #alias = #ty;
+            }
+        });
+        let tp = def
+            .params
+            .iter()
+            .rev()
+            .fold(def.return_type.clone(), |acc, (_, tp)| {
+                Tp::Arrow(Box::new(tp.clone()), Box::new(acc))
+            });
+        let tp = expand_type(&resolve_type(&tp, &mut Vec::new()));
+        quote::quote! {
+            #(#aliases)*
+            #[allow(non_camel_case_types)]
+            pub type #name = #ty;
+            #[allow(non_upper_case_globals)]
+            pub const #name: DefMarker<#name> = DefMarker(::core::marker::PhantomData);
+            const _: fn() = type_checks_as::<#name, #tp>;
+        }
+    });
+    quote::quote! { #(#items)* }
+}
@@ -0,0 +1,657 @@
+//! A native type checker and interpreter for `stlc_eval!`. They follow the
+//! rules of `WellTyped`, `Checks` and `Eval` in stlcrts, down to the shape
+//! of the terms they substitute into, so that the value computed here is
+//! the very type the trait-based evaluator would produce.
+
+use super::{DBExpr, Tp};
+
+/// The number of evaluation steps after which a program is considered
+/// not to terminate
+const FUEL: u64 = 10_000_000;
+
+/// Type of the variable i, the last type of the environment being index 0
+fn lookup(env: &[Tp], i: usize) -> Result<Tp, String> {
+    env.iter()
+        .rev()
+        .nth(i)
+        .cloned()
+        .ok_or_else(|| format!("unbound variable {i}"))
+}
+
+/// Type equality, the names of the type variables are only there for
+/// display so they are ignored
+fn same(a: &Tp, b: &Tp) -> bool {
+    let same_fields = |fs: &[(String, Tp)], gs: &[(String, Tp)]| {
+        fs.len() == gs.len()
+            && fs
+                .iter()
+                .zip(gs)
+                .all(|((l1, t1), (l2, t2))| l1 == l2 && same(t1, t2))
+    };
+    match (a, b) {
+        (Tp::Bool, Tp::Bool)
+        | (Tp::Nat, Tp::Nat)
+        | (Tp::Unit, Tp::Unit)
+        | (Tp::Top, Tp::Top)
+        | (Tp::Dyn, Tp::Dyn) => true,
+        (Tp::Ref(a), Tp::Ref(b)) | (Tp::Cont(a), Tp::Cont(b)) | (Tp::List(a), Tp::List(b)) => {
+            same(a, b)
+        }
+        (Tp::Record(fs), Tp::Record(gs)) | (Tp::Variant(fs), Tp::Variant(gs)) => {
+            same_fields(fs, gs)
+        }
+        (Tp::Arrow(a1, b1), Tp::Arrow(a2, b2)) => same(a1, a2) && same(b1, b2),
+        (Tp::TVar(i), Tp::TVar(j)) => i == j,
+        (Tp::Exists(_, a), Tp::Exists(_, b)) => same(a, b),
+        _ => false,
+    }
+}
+
+fn expect_same(found: &Tp, expected: &Tp) -> Result<(), String> {
+    if same(found, expected) {
+        Ok(())
+    } else {
+        Err(format!("expected type {expected:?}, found {found:?}"))
+    }
+}
+
+/// Rebuilds a type, replacing each type variable TVar(i) found under c
+/// binders by f(i, c)
+pub(crate) fn map_tvars(
+    tp: &Tp,
+    c: usize,
+    f: &impl Fn(usize, usize) -> Result<Tp, String>,
+) -> Result<Tp, String> {
+    let fields = |fs: &[(String, Tp)]| {
+        fs.iter()
+            .map(|(l, t)| Ok((l.clone(), map_tvars(t, c, f)?)))
+            .collect::<Result<Vec<_>, String>>()
+    };
+    Ok(match tp {
+        Tp::Bool | Tp::Nat | Tp::Unit | Tp::Top | Tp::Dyn | Tp::Var(_) => tp.clone(),
+        Tp::Ref(t) => Tp::Ref(Box::new(map_tvars(t, c, f)?)),
+        Tp::Cont(t) => Tp::Cont(Box::new(map_tvars(t, c, f)?)),
+        Tp::List(t) => Tp::List(Box::new(map_tvars(t, c, f)?)),
+        Tp::Record(fs) => Tp::Record(fields(fs)?),
+        Tp::Variant(cs) => Tp::Variant(fields(cs)?),
+        Tp::Arrow(a, b) => Tp::Arrow(Box::new(map_tvars(a, c, f)?), Box::new(map_tvars(b, c, f)?)),
+        Tp::TVar(i) => f(*i, c)?,
+        Tp::Exists(name, t) => Tp::Exists(name.clone(), Box::new(map_tvars(t, c + 1, f)?)),
+    })
+}
+
+fn shift_up(tp: &Tp) -> Tp {
+    map_tvars(tp, 0, &|i, c| Ok(Tp::TVar(if i >= c { i + 1 } else { i })))
+        .expect("shifting up cannot fail")
+}
+
+/// Fails if the type mentions the type variable 0, which is then out of scope
+fn shift_down(tp: &Tp) -> Result<Tp, String> {
+    map_tvars(tp, 0, &|i, c| {
+        if i == c {
+            Err("the hidden type of a package escapes its scope".to_string())
+        } else {
+            Ok(Tp::TVar(if i > c { i - 1 } else { i }))
+        }
+    })
+}
+
+/// Substitutes h for the type variable 0 of tp, which is removed
+fn ty_beta(tp: &Tp, h: &Tp) -> Tp {
+    map_tvars(tp, 0, &|i, c| {
+        Ok(if i == c {
+            (0..c).fold(h.clone(), |h, _| shift_up(&h))
+        } else {
+            Tp::TVar(if i > c { i - 1 } else { i })
+        })
+    })
+    .expect("substituting cannot fail")
+}
+
+fn unsupported(e: &DBExpr) -> String {
+    let feature = match e {
+        DBExpr::Ref(_) | DBExpr::Deref(_) | DBExpr::Assign(..) => "references are",
+        DBExpr::Print(_) => "print is",
+        DBExpr::Splice(_) => "spliced terms are",
+        DBExpr::Def(..) => "definitions of stlc_defs! are",
+        _ => "continuations are",
+    };
+    format!("{feature} not supported by stlc_eval!")
+}
+
+/// Synthesizes the type of e, like WellTyped
+pub(crate) fn synth(env: &mut Vec<Tp>, e: &DBExpr) -> Result<Tp, String> {
+    match e {
+        DBExpr::Unit => Ok(Tp::Unit),
+        DBExpr::True | DBExpr::False => Ok(Tp::Bool),
+        DBExpr::Nat(_) => Ok(Tp::Nat),
+        DBExpr::Var(i) => lookup(env, *i),
+        DBExpr::If(c, t, f) => {
+            expect_same(&synth(env, c)?, &Tp::Bool)?;
+            // Like T-If, the else branch gives the type if the then branch
+            // can only be checked
+            let (t, f) = match **t {
+                DBExpr::Raise(_) | DBExpr::Throw(..) | DBExpr::CLam(_) | DBExpr::CNil => (f, t),
+                _ => (t, f),
+            };
+            let tp = synth(env, t)?;
+            check(env, f, &tp)?;
+            Ok(tp)
+        }
+        DBExpr::Lam(tp, b) => {
+            env.push(tp.clone());
+            let r = synth(env, b)?;
+            env.pop();
+            Ok(Tp::Arrow(Box::new(tp.clone()), Box::new(r)))
+        }
+        DBExpr::CLam(_) => Err("cannot infer the parameter type of a function".to_string()),
+        DBExpr::App(f, x) => match synth(env, f)? {
+            Tp::Arrow(a, r) => {
+                check(env, x, &a)?;
+                Ok(*r)
+            }
+            tp => Err(format!("expected a function, found {tp:?}")),
+        },
+        DBExpr::Ann(e, tp) => {
+            check(env, e, tp)?;
+            Ok(tp.clone())
+        }
+        DBExpr::Let(t, b) => {
+            let tp = synth(env, t)?;
+            env.push(tp);
+            let r = synth(env, b)?;
+            env.pop();
+            Ok(r)
+        }
+        DBExpr::Fix(t) => match synth(env, t)? {
+            Tp::Arrow(a, b) if same(&a, &b) => Ok(*a),
+            tp => Err(format!(
+                "expected a function of type Tp -> Tp, found {tp:?}"
+            )),
+        },
+        DBExpr::FixN(fns, k) => {
+            // Every function is abstracted over the whole group
+            for (tp, f) in fns {
+                let curried = fns.iter().rev().fold(tp.clone(), |acc, (g, _)| {
+                    Tp::Arrow(Box::new(g.clone()), Box::new(acc))
+                });
+                expect_same(&synth(env, f)?, &curried)?;
+            }
+            Ok(fns[*k].0.clone())
+        }
+        DBExpr::NatRec(z, s, n) => {
+            let tp = synth(env, z)?;
+            check(env, s, &nat_rec_step(&tp))?;
+            expect_same(&synth(env, n)?, &Tp::Nat)?;
+            Ok(tp)
+        }
+        DBExpr::Raise(_) => Err("cannot infer the type of raise, annotate it".to_string()),
+        DBExpr::Try(t, h) => {
+            let tp = synth(env, t)?;
+            env.push(Tp::Nat);
+            check(env, h, &tp)?;
+            env.pop();
+            Ok(tp)
+        }
+        DBExpr::Record(fs) => Ok(Tp::Record(
+            fs.iter()
+                .map(|(l, f)| Ok((l.clone(), synth(env, f)?)))
+                .collect::<Result<_, String>>()?,
+        )),
+        DBExpr::Proj(e, l) => match synth(env, e)? {
+            Tp::Record(fs) => fs
+                .into_iter()
+                .find(|(name, _)| name == l)
+                .map(|(_, tp)| tp)
+                .ok_or_else(|| format!("no field {l}")),
+            tp => Err(format!("expected a record, found {tp:?}")),
+        },
+        DBExpr::Tag(l, e, tp) => match tp {
+            Tp::Variant(cs) => {
+                let (_, ctp) = cs
+                    .iter()
+                    .find(|(name, _)| name == l)
+                    .ok_or_else(|| format!("no case {l}"))?;
+                check(env, e, ctp)?;
+                Ok(tp.clone())
+            }
+            tp => Err(format!("expected a variant type, found {tp:?}")),
+        },
+        DBExpr::Match(e, bs) => {
+            let cs = variant_cases(env, e, bs)?;
+            let (l, ctp) = cs
+                .first()
+                .ok_or_else(|| "cannot infer the type of an empty match".to_string())?;
+            env.push(ctp.clone());
+            let tp = synth(env, branch(bs, l)?)?;
+            env.pop();
+            check_branches(env, &cs, bs, &tp)?;
+            Ok(tp)
+        }
+        DBExpr::CNil => Err("cannot infer the type of [], annotate it".to_string()),
+        DBExpr::Cons(h, t) => {
+            let tp = Tp::List(Box::new(synth(env, h)?));
+            check(env, t, &tp)?;
+            Ok(tp)
+        }
+        DBExpr::ListCase(l, n, c) => {
+            let etp = list_elem(env, l)?;
+            let tp = synth(env, n)?;
+            check_cons_branch(env, &etp, c, &tp)?;
+            Ok(tp)
+        }
+        DBExpr::Pack(h, e, tp) => match tp {
+            Tp::Exists(_, body) => {
+                check(env, e, &ty_beta(body, h))?;
+                Ok(tp.clone())
+            }
+            tp => Err(format!("expected an existential type, found {tp:?}")),
+        },
+        DBExpr::Unpack(e, b) => {
+            let xtp = package_body(env, e)?;
+            let mut env1: Vec<Tp> = env.iter().map(shift_up).collect();
+            env1.push(xtp);
+            shift_down(&synth(&mut env1, b)?)
+        }
+        DBExpr::IsZero(e) => {
+            expect_same(&synth(env, e)?, &Tp::Nat)?;
+            Ok(Tp::Bool)
+        }
+        DBExpr::Succ(e) | DBExpr::Pred(e) => {
+            expect_same(&synth(env, e)?, &Tp::Nat)?;
+            Ok(Tp::Nat)
+        }
+        // A definition is closed
+        DBExpr::Def(_, Some(t)) => synth(&mut Vec::new(), t),
+        DBExpr::Ref(_)
+        | DBExpr::Deref(_)
+        | DBExpr::Assign(..)
+        | DBExpr::Print(_)
+        | DBExpr::CallCC(_)
+        | DBExpr::Throw(..)
+        | DBExpr::Splice(_)
+        | DBExpr::Def(_, None) => Err(unsupported(e)),
+    }
+}
+
+/// Checks e against tp, like Checks
+pub(crate) fn check(env: &mut Vec<Tp>, e: &DBExpr, tp: &Tp) -> Result<(), String> {
+    match (e, tp) {
+        (DBExpr::CLam(b), Tp::Arrow(a, r)) => {
+            env.push((**a).clone());
+            check(env, b, r)?;
+            env.pop();
+            Ok(())
+        }
+        (DBExpr::Lam(p, b), Tp::Arrow(a, r)) => {
+            expect_same(p, a)?;
+            env.push(p.clone());
+            check(env, b, r)?;
+            env.pop();
+            Ok(())
+        }
+        (DBExpr::If(c, t, f), _) => {
+            expect_same(&synth(env, c)?, &Tp::Bool)?;
+            check(env, t, tp)?;
+            check(env, f, tp)
+        }
+        (DBExpr::Let(t, b), _) => {
+            let t = synth(env, t)?;
+            env.push(t);
+            check(env, b, tp)?;
+            env.pop();
+            Ok(())
+        }
+        (DBExpr::Fix(t), _) => check(
+            env,
+            t,
+            &Tp::Arrow(Box::new(tp.clone()), Box::new(tp.clone())),
+        ),
+        (DBExpr::NatRec(z, s, n), _) => {
+            check(env, z, tp)?;
+            check(env, s, &nat_rec_step(tp))?;
+            expect_same(&synth(env, n)?, &Tp::Nat)
+        }
+        (DBExpr::Raise(e), _) => check(env, e, &Tp::Nat),
+        (DBExpr::Try(t, h), _) => {
+            check(env, t, tp)?;
+            env.push(Tp::Nat);
+            check(env, h, tp)?;
+            env.pop();
+            Ok(())
+        }
+        (DBExpr::Record(fs), Tp::Record(tys)) => {
+            if fs.len() != tys.len() {
+                return Err(format!("expected a record of type {tp:?}"));
+            }
+            for ((l, f), (m, ftp)) in fs.iter().zip(tys) {
+                if l != m {
+                    return Err(format!("expected field {m}, found {l}"));
+                }
+                check(env, f, ftp)?;
+            }
+            Ok(())
+        }
+        (DBExpr::Match(e, bs), _) => {
+            let cs = variant_cases(env, e, bs)?;
+            check_branches(env, &cs, bs, tp)
+        }
+        (DBExpr::CNil, Tp::List(_)) => Ok(()),
+        (DBExpr::Cons(h, t), Tp::List(a)) => {
+            check(env, h, a)?;
+            check(env, t, tp)
+        }
+        (DBExpr::ListCase(l, n, c), _) => {
+            let etp = list_elem(env, l)?;
+            check(env, n, tp)?;
+            check_cons_branch(env, &etp, c, tp)
+        }
+        (DBExpr::Unpack(e, b), _) => {
+            let xtp = package_body(env, e)?;
+            let mut env1: Vec<Tp> = env.iter().map(shift_up).collect();
+            env1.push(xtp);
+            check(&mut env1, b, &shift_up(tp))
+        }
+        _ => expect_same(&synth(env, e)?, tp),
+    }
+}
+
+fn nat_rec_step(tp: &Tp) -> Tp {
+    Tp::Arrow(
+        Box::new(Tp::Nat),
+        Box::new(Tp::Arrow(Box::new(tp.clone()), Box::new(tp.clone()))),
+    )
+}
+
+fn variant_cases(
+    env: &mut Vec<Tp>,
+    e: &DBExpr,
+    bs: &[(String, DBExpr)],
+) -> Result<Vec<(String, Tp)>, String> {
+    match synth(env, e)? {
+        Tp::Variant(cs) if cs.len() == bs.len() => Ok(cs),
+        Tp::Variant(_) => Err("a match must have one branch per case".to_string()),
+        tp => Err(format!("expected a variant, found {tp:?}")),
+    }
+}
+
+fn branch<'a>(bs: &'a [(String, DBExpr)], l: &str) -> Result<&'a DBExpr, String> {
+    bs.iter()
+        .find(|(name, _)| name == l)
+        .map(|(_, b)| b)
+        .ok_or_else(|| format!("no branch for the case {l}"))
+}
+
+fn check_branches(
+    env: &mut Vec<Tp>,
+    cs: &[(String, Tp)],
+    bs: &[(String, DBExpr)],
+    tp: &Tp,
+) -> Result<(), String> {
+    for (l, ctp) in cs {
+        env.push(ctp.clone());
+        check(env, branch(bs, l)?, tp)?;
+        env.pop();
+    }
+    Ok(())
+}
+
+fn list_elem(env: &mut Vec<Tp>, l: &DBExpr) -> Result<Tp, String> {
+    match synth(env, l)? {
+        Tp::List(etp) => Ok(*etp),
+        tp => Err(format!("expected a list, found {tp:?}")),
+    }
+}
+
+/// The head is bound to index 1 and the tail to index 0
+fn check_cons_branch(env: &mut Vec<Tp>, etp: &Tp, c: &DBExpr, tp: &Tp) -> Result<(), String> {
+    env.push(etp.clone());
+    env.push(Tp::List(Box::new(etp.clone())));
+    check(env, c, tp)?;
+    env.pop();
+    env.pop();
+    Ok(())
+}
+
+fn package_body(env: &mut Vec<Tp>, e: &DBExpr) -> Result<Tp, String> {
+    match synth(env, e)? {
+        Tp::Exists(_, xtp) => Ok(*xtp),
+        tp => Err(format!("expected a package, found {tp:?}")),
+    }
+}
+
+/// [c -> v] with the variables above c lowered by one, v is a closed value
+fn subst(e: &DBExpr, v: &DBExpr, c: usize) -> DBExpr {
+    e.map_vars(c, &|i, c| match i.cmp(&c) {
+        std::cmp::Ordering::Less => DBExpr::Var(i),
+        std::cmp::Ordering::Equal => v.clone(),
+        std::cmp::Ordering::Greater => DBExpr::Var(i - 1),
+    })
+}
+
+fn is_value(e: &DBExpr) -> bool {
+    match e {
+        DBExpr::Unit
+        | DBExpr::True
+        | DBExpr::False
+        | DBExpr::Nat(_)
+        | DBExpr::Lam(..)
+        | DBExpr::CLam(_)
+        | DBExpr::CNil => true,
+        DBExpr::Succ(v) | DBExpr::Raise(v) => is_value(v),
+        DBExpr::Record(fs) => fs.iter().all(|(_, v)| is_value(v)),
+        DBExpr::Tag(_, v, _) | DBExpr::Pack(_, v, _) => is_value(v),
+        DBExpr::Cons(h, t) => is_value(h) && is_value(t),
+        _ => false,
+    }
+}
+
+/// Evaluates a closed term, a raised exception is the value Raise(v)
+pub(crate) fn eval(e: &DBExpr) -> Result<DBExpr, String> {
+    Interpreter {
+        fuel: FUEL,
+        stack: Vec::new(),
+    }
+    .run(e.clone())
+}
+
+/// What is left to do with the value of the subterm being evaluated, the
+/// stack of them is kept on the heap rather than as Rust calls so that
+/// deep recursions do not overflow the stack of rustc
+enum Frame {
+    If(Box<DBExpr>, Box<DBExpr>),
+    AppFun(Box<DBExpr>),
+    AppArg(Box<DBExpr>),
+    Let(Box<DBExpr>),
+    Fix,
+    NatRec(Box<DBExpr>, Box<DBExpr>),
+    Raise,
+    Try(Box<DBExpr>),
+    /// The fields evaluated so far, the label of the one being evaluated
+    /// and the fields left
+    Record(Vec<(String, DBExpr)>, String, Vec<(String, DBExpr)>),
+    Proj(String),
+    Tag(String, Tp),
+    Match(Vec<(String, DBExpr)>),
+    ConsHead(Box<DBExpr>),
+    ConsTail(DBExpr),
+    ListCase(Box<DBExpr>, Box<DBExpr>),
+    Pack(Tp, Tp),
+    Unpack(Box<DBExpr>),
+    IsZero,
+    Succ,
+    Pred,
+}
+
+/// A step of the interpreter, either a term to evaluate or the value to
+/// give to the frame on top of the stack
+enum Step {
+    Eval(DBExpr),
+    Return(DBExpr),
+}
+
+struct Interpreter {
+    fuel: u64,
+    stack: Vec<Frame>,
+}
+
+impl Interpreter {
+    fn run(&mut self, e: DBExpr) -> Result<DBExpr, String> {
+        let mut step = Step::Eval(e);
+        loop {
+            step = match step {
+                Step::Eval(e) => {
+                    self.fuel = self
+                        .fuel
+                        .checked_sub(1)
+                        .ok_or_else(|| format!("evaluation did not terminate in {FUEL} steps"))?;
+                    self.eval(e)?
+                }
+                // An exception unwinds the frames up to the closest try
+                Step::Return(DBExpr::Raise(p)) => loop {
+                    match self.stack.pop() {
+                        None => return Ok(DBExpr::Raise(p)),
+                        Some(Frame::Try(h)) => break Step::Eval(subst(&h, &p, 0)),
+                        Some(_) => {}
+                    }
+                },
+                Step::Return(v) => match self.stack.pop() {
+                    None => return Ok(v),
+                    Some(frame) => self.resume(frame, v)?,
+                },
+            }
+        }
+    }
+
+    fn eval(&mut self, e: DBExpr) -> Result<Step, String> {
+        let mut push = |frame, e: Box<DBExpr>| {
+            self.stack.push(frame);
+            Ok(Step::Eval(*e))
+        };
+        match e {
+            DBExpr::Unit
+            | DBExpr::True
+            | DBExpr::False
+            | DBExpr::Nat(_)
+            | DBExpr::Lam(..)
+            | DBExpr::CLam(_)
+            | DBExpr::CNil => Ok(Step::Return(e)),
+            DBExpr::Var(i) => Err(format!("free variable {i}")),
+            DBExpr::If(c, t, f) => push(Frame::If(t, f), c),
+            DBExpr::App(f, x) => push(Frame::AppFun(x), f),
+            DBExpr::Ann(e, _) => Ok(Step::Eval(*e)),
+            DBExpr::Let(t, b) => push(Frame::Let(b), t),
+            DBExpr::Fix(t) => push(Frame::Fix, t),
+            DBExpr::FixN(fns, k) => {
+                let mut t = fns[k].1.clone();
+                for j in 0..fns.len() {
+                    t = match t {
+                        DBExpr::Lam(_, b) => subst(&b, &DBExpr::FixN(fns.clone(), j), 0),
+                        v => return stuck(&v),
+                    };
+                }
+                Ok(Step::Eval(t))
+            }
+            DBExpr::NatRec(z, s, n) => push(Frame::NatRec(z, s), n),
+            DBExpr::Raise(e) => push(Frame::Raise, e),
+            DBExpr::Try(t, h) => push(Frame::Try(h), t),
+            DBExpr::Record(fs) => {
+                let mut rest = fs.into_iter();
+                match rest.next() {
+                    Some((l, f)) => push(Frame::Record(Vec::new(), l, rest.collect()), Box::new(f)),
+                    None => Ok(Step::Return(DBExpr::Record(Vec::new()))),
+                }
+            }
+            DBExpr::Proj(e, l) => push(Frame::Proj(l), e),
+            DBExpr::Tag(l, e, tp) => push(Frame::Tag(l, tp), e),
+            DBExpr::Match(e, bs) => push(Frame::Match(bs), e),
+            DBExpr::Cons(h, t) => push(Frame::ConsHead(t), h),
+            DBExpr::ListCase(l, n, c) => push(Frame::ListCase(n, c), l),
+            DBExpr::Pack(h, e, tp) => push(Frame::Pack(h, tp), e),
+            DBExpr::Unpack(e, b) => push(Frame::Unpack(b), e),
+            DBExpr::IsZero(e) => push(Frame::IsZero, e),
+            DBExpr::Succ(e) => push(Frame::Succ, e),
+            DBExpr::Pred(e) => push(Frame::Pred, e),
+            DBExpr::Def(_, Some(t)) => Ok(Step::Eval(*t)),
+            DBExpr::Ref(_)
+            | DBExpr::Deref(_)
+            | DBExpr::Assign(..)
+            | DBExpr::Print(_)
+            | DBExpr::CallCC(_)
+            | DBExpr::Throw(..)
+            | DBExpr::Splice(_)
+            | DBExpr::Def(_, None) => Err(unsupported(&e)),
+        }
+    }
+
+    /// Gives the value v, which is not an exception, to frame
+    fn resume(&mut self, frame: Frame, v: DBExpr) -> Result<Step, String> {
+        let eval = |e: DBExpr| Ok(Step::Eval(e));
+        let ret = |v: DBExpr| Ok(Step::Return(v));
+        match (frame, v) {
+            (Frame::If(t, _), DBExpr::True) => eval(*t),
+            (Frame::If(_, f), DBExpr::False) => eval(*f),
+            (Frame::AppFun(x), DBExpr::Lam(_, b) | DBExpr::CLam(b)) => {
+                self.stack.push(Frame::AppArg(b));
+                eval(*x)
+            }
+            (Frame::AppArg(b) | Frame::Let(b), v) => eval(subst(&b, &v, 0)),
+            // E-Fix does not evaluate the unfolded body, which must be a value
+            (Frame::Fix, f @ (DBExpr::Lam(..) | DBExpr::CLam(_))) => {
+                let (DBExpr::Lam(_, b) | DBExpr::CLam(b)) = &f else {
+                    unreachable!()
+                };
+                let r = subst(b, &DBExpr::Fix(Box::new(f.clone())), 0);
+                if is_value(&r) { ret(r) } else { stuck(&r) }
+            }
+            (Frame::NatRec(z, _), DBExpr::Nat(0)) => eval(*z),
+            (Frame::NatRec(z, s), DBExpr::Nat(m)) => {
+                let p = Box::new(DBExpr::Nat(m - 1));
+                let rec = DBExpr::NatRec(z, s.clone(), p.clone());
+                eval(DBExpr::App(Box::new(DBExpr::App(s, p)), Box::new(rec)))
+            }
+            (Frame::Raise, v) => ret(DBExpr::Raise(Box::new(v))),
+            (Frame::Try(_), v) => ret(v),
+            (Frame::Record(mut vs, l, rest), v) => {
+                vs.push((l, v));
+                let mut rest = rest.into_iter();
+                match rest.next() {
+                    Some((l, f)) => {
+                        self.stack.push(Frame::Record(vs, l, rest.collect()));
+                        eval(f)
+                    }
+                    None => ret(DBExpr::Record(vs)),
+                }
+            }
+            (Frame::Proj(l), DBExpr::Record(vs)) => {
+                match vs.into_iter().find(|(name, _)| *name == l) {
+                    Some((_, v)) => ret(v),
+                    None => Err(format!("no field {l}")),
+                }
+            }
+            (Frame::Tag(l, tp), v) => ret(DBExpr::Tag(l, Box::new(v), tp)),
+            (Frame::Match(bs), DBExpr::Tag(l, p, _)) => eval(subst(branch(&bs, &l)?, &p, 0)),
+            (Frame::ConsHead(t), h) => {
+                self.stack.push(Frame::ConsTail(h));
+                eval(*t)
+            }
+            (Frame::ConsTail(h), t) => ret(DBExpr::Cons(Box::new(h), Box::new(t))),
+            (Frame::ListCase(n, _), DBExpr::CNil) => eval(*n),
+            (Frame::ListCase(_, c), DBExpr::Cons(h, t)) => {
+                eval(DBExpr::Let(h, Box::new(DBExpr::Let(t, c))))
+            }
+            (Frame::Pack(h, tp), v) => ret(DBExpr::Pack(h, Box::new(v), tp)),
+            (Frame::Unpack(b), DBExpr::Pack(_, v, _)) => eval(subst(&b, &v, 0)),
+            (Frame::IsZero, DBExpr::Nat(n)) => {
+                ret(if n == 0 { DBExpr::True } else { DBExpr::False })
+            }
+            (Frame::Succ, DBExpr::Nat(n)) => ret(DBExpr::Nat(n + 1)),
+            (Frame::Pred, DBExpr::Nat(n)) => ret(DBExpr::Nat(n.saturating_sub(1))),
+            (_, v) => stuck(&v),
+        }
+    }
+}
+
+fn stuck<T>(v: &DBExpr) -> Result<T, String> {
+    Err(format!("evaluation is stuck on {v:?}"))
+}
@@ -0,0 +1,147 @@
+//! The optimizer enabled by `#![opt]`, which simplifies a program before
+//! it is expanded so that rustc has less to type-check and evaluate. Unused
+//! lets are dropped, lets used once are inlined and `succ`, `pred` and
+//! `iszero` of literals are folded.
+
+use super::{DBExpr, Tp, native};
+use std::cell::Cell;
+
+/// Optimizes a program whose free variables have the types of env, the
+/// first variable last. Only programs the native type checker accepts are
+/// optimized: dropping a let could otherwise hide a type error from rustc.
+pub(crate) fn optimize(e: DBExpr, env: &[Tp]) -> DBExpr {
+    if native::synth(&mut env.to_vec(), &e).is_ok() {
+        opt(&e)
+    } else {
+        e
+    }
+}
+
+fn opt(e: &DBExpr) -> DBExpr {
+    simplify(e.map_children(|e, _, _| opt(e)))
+}
+
+/// Simplifies a term whose subterms are already optimized
+fn simplify(e: DBExpr) -> DBExpr {
+    match e {
+        DBExpr::Let(t, b) if pure(&t) => match occurrences(&b) {
+            0 => b.map_vars(0, &|i, c| DBExpr::Var(if i > c { i - 1 } else { i })),
+            // Inlining may give new literals to fold
+            1 => opt(&inline(&b, &t)),
+            _ => DBExpr::Let(t, b),
+        },
+        // succ, pred and iszero are functions, applying them gives back the
+        // primitive when the argument has the same type in checking mode
+        DBExpr::App(f, a) => match primitive(&f) {
+            Some(prim) if synthesizes(&a) => simplify(prim(a)),
+            _ => DBExpr::App(f, a),
+        },
+        DBExpr::Succ(n) => match *n {
+            DBExpr::Nat(n) => DBExpr::Nat(n + 1),
+            n => DBExpr::Succ(Box::new(n)),
+        },
+        DBExpr::Pred(n) => match *n {
+            DBExpr::Nat(n) => DBExpr::Nat(n.saturating_sub(1)),
+            n => DBExpr::Pred(Box::new(n)),
+        },
+        DBExpr::IsZero(n) => match *n {
+            DBExpr::Nat(0) => DBExpr::True,
+            DBExpr::Nat(_) => DBExpr::False,
+            n => DBExpr::IsZero(Box::new(n)),
+        },
+        e => e,
+    }
+}
+
+/// The primitive f is the eta-expansion of, if any
+fn primitive(f: &DBExpr) -> Option<fn(Box<DBExpr>) -> DBExpr> {
+    let DBExpr::Lam(Tp::Nat, b) = f else {
+        return None;
+    };
+    match &**b {
+        DBExpr::Succ(v) if **v == DBExpr::Var(0) => Some(DBExpr::Succ),
+        DBExpr::Pred(v) if **v == DBExpr::Var(0) => Some(DBExpr::Pred),
+        DBExpr::IsZero(v) if **v == DBExpr::Var(0) => Some(DBExpr::IsZero),
+        _ => None,
+    }
+}
+
+/// Whether e is typed the same way whether it is checked or synthesized
+fn synthesizes(e: &DBExpr) -> bool {
+    matches!(
+        e,
+        DBExpr::Nat(_)
+            | DBExpr::Var(_)
+            | DBExpr::App(..)
+            | DBExpr::Ann(..)
+            | DBExpr::Succ(_)
+            | DBExpr::Pred(_)
+            | DBExpr::Proj(..)
+    )
+}
+
+/// Whether evaluating e terminates without raising or other effects, so
+/// that it can be dropped or moved without changing what the program does
+fn pure(e: &DBExpr) -> bool {
+    match e {
+        DBExpr::Unit
+        | DBExpr::True
+        | DBExpr::False
+        | DBExpr::Nat(_)
+        | DBExpr::Var(_)
+        | DBExpr::Lam(..)
+        | DBExpr::CLam(_)
+        | DBExpr::CNil
+        | DBExpr::Def(..) => true,
+        DBExpr::Ann(e, _)
+        | DBExpr::Succ(e)
+        | DBExpr::Pred(e)
+        | DBExpr::IsZero(e)
+        | DBExpr::Proj(e, _)
+        | DBExpr::Tag(_, e, _)
+        | DBExpr::Pack(_, e, _) => pure(e),
+        DBExpr::If(c, t, e) => pure(c) && pure(t) && pure(e),
+        DBExpr::Cons(h, t) => pure(h) && pure(t),
+        DBExpr::Record(fs) => fs.iter().all(|(_, e)| pure(e)),
+        _ => false,
+    }
+}
+
+/// Number of occurrences of the variable 0 in e
+fn occurrences(e: &DBExpr) -> usize {
+    let count = Cell::new(0);
+    e.map_vars(0, &|i, c| {
+        if i == c {
+            count.set(count.get() + 1);
+        }
+        DBExpr::Var(i)
+    });
+    count.get()
+}
+
+/// [0 -> t] in b, with the variables above 0 lowered by one and those of t
+/// shifted past the binders it ends up under
+fn inline(b: &DBExpr, t: &DBExpr) -> DBExpr {
+    inline_under(b, t, 0, 0)
+}
+
+/// Inlines t in e found under c binders and d type binders of b, the type
+/// variables of t are shifted past the unpacks it ends up under
+fn inline_under(e: &DBExpr, t: &DBExpr, c: usize, d: usize) -> DBExpr {
+    match e {
+        DBExpr::Var(i) => match i.cmp(&c) {
+            std::cmp::Ordering::Less => DBExpr::Var(*i),
+            std::cmp::Ordering::Equal => t
+                .map_vars(0, &|j, k| DBExpr::Var(if j >= k { j + c } else { j }))
+                .map_tys(0, &|tp, k| shift_tys(tp, k, d)),
+            std::cmp::Ordering::Greater => DBExpr::Var(i - 1),
+        },
+        _ => e.map_children(|e, k, l| inline_under(e, t, c + k, d + l)),
+    }
+}
+
+/// Raises by n the type variables of tp which are free under k type binders
+fn shift_tys(tp: &Tp, k: usize, n: usize) -> Tp {
+    native::map_tvars(tp, k, &|i, c| Ok(Tp::TVar(if i >= c { i + n } else { i })))
+        .expect("shifting cannot fail")
+}
@@ -0,0 +1,48 @@
+//! The definitions of the prelude. They are kept here rather than in
+//! stlcrts, whose prelude module expands them with `prelude_defs!`, so that
+//! the terms of the ones a program uses are known to stlc_eval! and #![opt].
+
+use super::{DBExpr, Def, lower_def, parse_defs};
+use syn::Ident;
+
+const SOURCE: &str = "
+    def not (b: Bool) : Bool = if b then false else true;
+    def and (a: Bool) (b: Bool) : Bool = if a then b else false;
+    def or (a: Bool) (b: Bool) : Bool = if a then true else b;
+    def add (a: Nat) (b: Nat) : Nat = if iszero a then b else succ (add (pred a) b);
+    def mul (a: Nat) (b: Nat) : Nat = if iszero a then 0 else add b (mul (pred a) b);
+    def eq (a: Nat) (b: Nat) : Bool =
+        if iszero a then iszero b else if iszero b then false else eq (pred a) (pred b);
+    def le (a: Nat) (b: Nat) : Bool =
+        if iszero a then true else if iszero b then false else le (pred a) (pred b);
+    def fact (n: Nat) : Nat = if iszero n then 1 else mul n (fact (pred n));
+";
+
+pub(crate) fn defs() -> Vec<Def> {
+    syn::parse::Parser::parse_str(parse_defs, SOURCE).expect("the prelude parses")
+}
+
+/// The terms of the definitions, in which the earlier definitions they
+/// refer to are known as well
+pub(crate) fn terms() -> Vec<(Ident, DBExpr)> {
+    let mut terms: Vec<(Ident, DBExpr)> = Vec::new();
+    for def in defs() {
+        let term = know(&lower_def(&def), &terms);
+        terms.push((def.name, term));
+    }
+    terms
+}
+
+/// Gives the references of e to the definitions of known their term
+fn know(e: &DBExpr, known: &[(Ident, DBExpr)]) -> DBExpr {
+    match e {
+        DBExpr::Def(path, None) => match known.iter().find(|(name, _)| path.is_ident(name)) {
+            Some((name, term)) => DBExpr::Def(
+                syn::parse_quote! { prelude::#name },
+                Some(Box::new(term.clone())),
+            ),
+            None => e.clone(),
+        },
+        _ => e.map_children(|e, _, _| know(e, known)),
+    }
+}